@@ -1,131 +1,281 @@
-use std::cmp::max;
+use std::cmp::{max, Ordering};
+use std::iter::FromIterator;
 
 // https://www.cs.umd.edu/class/fall2019/cmsc420-0201/Lects/lect06-aa.pdf
 // https://people.ksp.sk/~kuko/gnarley-trees/AAtree.html
 #[derive(Clone)]
-struct Node {
-    value: u32,
+struct Node<T> {
+    value: T,
     level: u32,
-    left: Option<Box<Node>>,
-    right: Option<Box<Node>>,
+    left: Option<Box<Node<T>>>,
+    right: Option<Box<Node<T>>>,
 }
 
-impl Node {
-    pub fn new_leaf(value: u32) -> Node {
+impl<T: Ord> Node<T> {
+    pub fn new_leaf(value: T) -> Node<T> {
         Node::new(value, 1)
     }
-    pub fn new(value: u32, level: u32) -> Node {
+    pub fn new(value: T, level: u32) -> Node<T> {
         Node { value, level, left: None, right: None }
     }
 
-    pub fn put(&self, value: u32) -> Node {
-        fn inner(current: &Node, value: u32) -> Node {
+    /// Adds the value to the subtree in place, returning whether it was not
+    /// in the subtree yet. Only the nodes on the path to the value are
+    /// touched, unlike `put`, which copies the rest of the tree.
+    fn insert(subtree: &mut Option<Box<Node<T>>>, value: T) -> bool {
+        let mut node = match subtree.take() {
+            Some(node) => node,
+            None => {
+                *subtree = Some(Box::new(Node::new_leaf(value)));
+                return true;
+            }
+        };
+        let inserted = match value.cmp(&node.value) {
+            Ordering::Less => Node::insert(&mut node.left, value),
+            Ordering::Greater => Node::insert(&mut node.right, value),
+            Ordering::Equal => false,
+        };
+        *subtree = Some(if inserted { split(skew(node)) } else { node });
+        inserted
+    }
+
+    fn traverse<F>(node: &Node<T>, f: &mut F)
+    where
+        F: FnMut(&Node<T>),
+    {
+        if let Some(left) = node.left.as_ref() {
+            Node::traverse(left, f);
+        }
+        f(node);
+        if let Some(right) = node.right.as_ref() {
+            Node::traverse(right, f);
+        }
+    }
+
+    pub fn depth(&self) -> u32 {
+        match (self.left.as_ref(), self.right.as_ref()) {
+            (Some(left), Some(right)) => max(left.depth(), right.depth()) + 1,
+            (Some(left), None) => left.depth() + 1,
+            (None, Some(right)) => right.depth() + 1,
+            (None, None) => 1,
+        }
+    }
+}
+
+impl<T: Ord + Clone> Node<T> {
+    /// Returns a new tree with the value added, leaving this one as it is.
+    pub fn put(&self, value: T) -> Node<T> {
+        fn inner<T: Ord + Clone>(current: &Node<T>, value: T) -> Node<T> {
             let inserted_node = if current.value == value {
                 current.clone()
             } else if value < current.value {
                 Node {
-                    value: current.value,
+                    value: current.value.clone(),
                     level: current.level,
-                    left: Some(Box::new(
-                        current.left.as_ref().map_or_else(|| Node::new(value, current.level), |n| inner(n, value)),
-                    )),
+                    left: Some(Box::new(match current.left.as_ref() {
+                        Some(n) => inner(n, value),
+                        None => Node::new(value, current.level),
+                    })),
                     right: current.right.clone(),
                 }
             } else {
                 Node {
-                    value: current.value,
+                    value: current.value.clone(),
                     level: current.level,
                     left: current.left.clone(),
-                    right: Some(Box::new(
-                        current.right.as_ref().map_or_else(|| Node::new(value, current.level), |n| inner(n, value)),
-                    )),
+                    right: Some(Box::new(match current.right.as_ref() {
+                        Some(n) => inner(n, value),
+                        None => Node::new(value, current.level),
+                    })),
                 }
             };
 
-            split(skew(inserted_node))
-        }
-
-        fn skew(mut node: Node) -> Node {
-            match node.left.take() {
-                None => node,
-                Some(mut left) => {
-                    if left.level == node.level {
-                        node.left = left.right.take();
-                        left.right = Some(Box::new(node));
-                        *left
-                    } else {
-                        node.left = Some(left);
-                        node
-                    }
-                }
-            }
-        }
-
-        fn split(mut node: Node) -> Node {
-            match node.right.take() {
-                None => node,
-                Some(mut right) => match right.right.as_ref() {
-                    None => {
-                        node.right = Some(right);
-                        node
-                    }
-                    Some(right_right) => {
-                        if node.level == right.level && right.level == right_right.level {
-                            node.right = right.left.take();
-                            right.left = Some(Box::new(node));
-                            right.level += 1;
-                            *right
-                        } else {
-                            node.right = Some(right);
-                            node
-                        }
-                    }
-                },
-            }
+            *split(skew(Box::new(inserted_node)))
         }
 
         inner(self, value)
     }
 
-    pub fn collect_to_vec(&self) -> (Vec<u32>, Vec<u32>) {
+    pub fn collect_to_vec(&self) -> (Vec<T>, Vec<u32>) {
         let mut values = vec![];
         let mut levels = vec![];
         Node::traverse(self, &mut |node| {
-            values.push(node.value);
+            values.push(node.value.clone());
             levels.push(node.level)
         });
         (values, levels)
     }
+}
 
-    fn traverse<F>(node: &Node, f: &mut F)
-    where
-        F: FnMut(&Node),
-    {
-        if let Some(left) = node.left.as_ref() {
-            Node::traverse(left, f);
+fn skew<T>(mut node: Box<Node<T>>) -> Box<Node<T>> {
+    match node.left.take() {
+        None => node,
+        Some(mut left) => {
+            if left.level == node.level {
+                node.left = left.right.take();
+                left.right = Some(node);
+                left
+            } else {
+                node.left = Some(left);
+                node
+            }
         }
-        f(node);
-        if let Some(right) = node.right.as_ref() {
-            Node::traverse(right, f);
+    }
+}
+
+fn split<T>(mut node: Box<Node<T>>) -> Box<Node<T>> {
+    match node.right.take() {
+        None => node,
+        Some(mut right) => match right.right.as_ref() {
+            None => {
+                node.right = Some(right);
+                node
+            }
+            Some(right_right) => {
+                if node.level == right.level && right.level == right_right.level {
+                    node.right = right.left.take();
+                    right.left = Some(node);
+                    right.level += 1;
+                    right
+                } else {
+                    node.right = Some(right);
+                    node
+                }
+            }
+        },
+    }
+}
+
+/// An ordered set on top of `Node`, which may be empty.
+#[derive(Clone)]
+pub struct AATree<T> {
+    root: Option<Box<Node<T>>>,
+    len: usize,
+}
+
+impl<T: Ord> AATree<T> {
+    pub fn new() -> Self {
+        AATree { root: None, len: 0 }
+    }
+
+    /// Adds the value, returning whether it was not in the tree yet.
+    pub fn insert(&mut self, value: T) -> bool {
+        let inserted = Node::insert(&mut self.root, value);
+        if inserted {
+            self.len += 1;
         }
+        inserted
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        let mut current = self.root.as_deref();
+        while let Some(node) = current {
+            current = match value.cmp(&node.value) {
+                Ordering::Less => node.left.as_deref(),
+                Ordering::Greater => node.right.as_deref(),
+                Ordering::Equal => return true,
+            };
+        }
+        false
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn min(&self) -> Option<&T> {
+        let mut node = self.root.as_deref()?;
+        while let Some(left) = node.left.as_deref() {
+            node = left;
+        }
+        Some(&node.value)
+    }
+
+    pub fn max(&self) -> Option<&T> {
+        let mut node = self.root.as_deref()?;
+        while let Some(right) = node.right.as_deref() {
+            node = right;
+        }
+        Some(&node.value)
+    }
+
+    /// Iterates over the values in ascending order.
+    pub fn iter(&self) -> Iter<T> {
+        let mut iter = Iter { stack: vec![] };
+        iter.push_left(self.root.as_deref());
+        iter
     }
 
     pub fn depth(&self) -> u32 {
-        match (self.left.as_ref(), self.right.as_ref()) {
-            (Some(left), Some(right)) => max(left.depth(), right.depth()) + 1,
-            (Some(left), None) => left.depth() + 1,
-            (None, Some(right)) => right.depth() + 1,
-            (None, None) => 1,
+        self.root.as_deref().map_or(0, Node::depth)
+    }
+}
+
+impl<T: Ord> Default for AATree<T> {
+    fn default() -> Self {
+        AATree::new()
+    }
+}
+
+impl<T: Ord> FromIterator<T> for AATree<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut tree = AATree::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<T: Ord> Extend<T> for AATree<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl<'a, T: Ord> IntoIterator for &'a AATree<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An in-order iterator over an `AATree`. The stack holds the nodes whose
+/// value and right subtree are still to be visited.
+pub struct Iter<'a, T> {
+    stack: Vec<&'a Node<T>>,
+}
+
+impl<'a, T> Iter<'a, T> {
+    fn push_left(&mut self, mut node: Option<&'a Node<T>>) {
+        while let Some(current) = node {
+            self.stack.push(current);
+            node = current.left.as_deref();
         }
     }
 }
 
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left(node.right.as_deref());
+        Some(&node.value)
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use quickcheck::TestResult;
     use quickcheck_macros::quickcheck;
 
-    use crate::aa_tree::Node;
+    use crate::aa_tree::{AATree, Node};
 
     extern crate quickcheck_macros;
 
@@ -201,23 +351,78 @@ mod test {
         assert_eq!(levels, vec![1, 1, 2, 1, 3, 1, 1, 2, 1, 2, 1, 3, 1, 2, 1, 2, 1]);
     }
 
+    #[test]
+    fn should_start_empty() {
+        let tree: AATree<u32> = AATree::new();
+
+        assert!(tree.is_empty());
+        assert_eq!(tree.len(), 0);
+        assert!(!tree.contains(&1));
+        assert_eq!(tree.min(), None);
+        assert_eq!(tree.max(), None);
+        assert_eq!(tree.iter().next(), None);
+        assert_eq!(tree.depth(), 0);
+    }
+
+    #[test]
+    fn should_insert_into_set() {
+        let mut tree = AATree::new();
+
+        assert!(tree.insert(5));
+        assert!(tree.insert(2));
+        assert!(!tree.insert(5));
+        assert!(tree.insert(9));
+
+        assert_eq!(tree.len(), 3);
+        assert!(tree.contains(&2) && tree.contains(&5) && tree.contains(&9));
+        assert!(!tree.contains(&3));
+        assert_eq!(tree.min(), Some(&2));
+        assert_eq!(tree.max(), Some(&9));
+        assert_eq!(tree.iter().copied().collect::<Vec<_>>(), vec![2, 5, 9]);
+    }
+
+    #[test]
+    fn should_collect_and_extend() {
+        let mut tree: AATree<String> = ["pear", "apple", "fig", "apple"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(tree.len(), 3);
+
+        tree.extend(vec!["kiwi".to_string(), "fig".to_string()]);
+        let values: Vec<&str> = (&tree).into_iter().map(String::as_str).collect();
+        assert_eq!(values, vec!["apple", "fig", "kiwi", "pear"]);
+        assert_eq!(tree.len(), 4);
+    }
+
+    #[test]
+    fn should_hold_values_that_cannot_be_cloned() {
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+        struct Id(u32);
+
+        let tree: AATree<Id> = [Id(3), Id(1), Id(3)].into_iter().collect();
+
+        assert_eq!(tree.len(), 2);
+        assert!(tree.contains(&Id(1)));
+        assert_eq!(tree.iter().collect::<Vec<_>>(), vec![&Id(1), &Id(3)]);
+    }
+
+    #[test]
+    fn should_insert_many_values() {
+        let tree: AATree<u32> = (0..100_000).chain((0..100_000).rev()).collect();
+
+        assert_eq!(tree.len(), 100_000);
+        assert!(tree.iter().copied().eq(0..100_000));
+        assert!(tree.depth() <= 2 * 16 + 1);
+    }
+
     #[quickcheck]
-    fn should_build_balanced_tree(mut input: Vec<u32>) -> TestResult {
-        if input.is_empty() {
-            TestResult::discard()
-        } else {
-            let (first, rest) = input.split_at(1);
-            let node = Node::new_leaf(*first.get(0).unwrap());
-            let node = rest.iter().fold(node, |acc, v| acc.put(*v));
-
-            let (values, _) = node.collect_to_vec();
-            input.sort();
-            input.dedup();
-
-            let depth = node.depth();
-            let log2_len = input.len().ilog2();
-
-            TestResult::from_bool(values == input && depth <= log2_len * 2 + 1)
-        }
+    fn should_build_balanced_tree(mut input: Vec<u32>) -> bool {
+        let tree: AATree<u32> = input.iter().copied().collect();
+
+        input.sort();
+        input.dedup();
+
+        let values: Vec<u32> = tree.iter().copied().collect();
+        let depth_limit = input.len().checked_ilog2().map_or(0, |log2_len| log2_len * 2 + 1);
+
+        values == input && tree.len() == input.len() && tree.depth() <= depth_limit
     }
 }
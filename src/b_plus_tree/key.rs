@@ -0,0 +1,64 @@
+use std::mem::size_of;
+
+/// A key of a fixed width, so that every node page holds a fixed number of
+/// entries. Keys are compared after decoding, the encoding does not have to
+/// preserve the order.
+pub trait FixedKey: Ord + Copy {
+    const SIZE: usize;
+
+    /// Writes the key into `buf`, which is exactly `SIZE` bytes long.
+    fn encode(&self, buf: &mut [u8]);
+
+    fn decode(buf: &[u8]) -> Self;
+}
+
+macro_rules! impl_fixed_key {
+    ($($t:ty),*) => {
+        $(
+            impl FixedKey for $t {
+                const SIZE: usize = size_of::<$t>();
+
+                fn encode(&self, buf: &mut [u8]) {
+                    buf.copy_from_slice(&self.to_le_bytes());
+                }
+
+                fn decode(buf: &[u8]) -> Self {
+                    <$t>::from_le_bytes(buf.try_into().unwrap())
+                }
+            }
+        )*
+    };
+}
+
+impl_fixed_key!(u16, u32, u64, i32, i64);
+
+impl<const N: usize> FixedKey for [u8; N] {
+    const SIZE: usize = N;
+
+    fn encode(&self, buf: &mut [u8]) {
+        buf.copy_from_slice(self);
+    }
+
+    fn decode(buf: &[u8]) -> Self {
+        buf.try_into().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FixedKey;
+
+    fn round_trip<K: FixedKey + std::fmt::Debug>(key: K) {
+        let mut buf = vec![0; K::SIZE];
+        key.encode(&mut buf);
+        assert_eq!(K::decode(&buf), key);
+    }
+
+    #[test]
+    fn should_round_trip_keys() {
+        round_trip(7u16);
+        round_trip(u32::MAX);
+        round_trip(-5i64);
+        round_trip(*b"fixed key");
+    }
+}
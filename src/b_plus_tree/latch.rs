@@ -0,0 +1,118 @@
+use std::io;
+
+use crate::buffer::buffer_pool::{no_free_frame, page_not_deallocated};
+use crate::buffer::buffer_pool_instance::BufferPoolInstance;
+use crate::buffer::page::{PageLatch, PageReadGuard, PageWriteGuard};
+use crate::buffer::types::PageId;
+
+use super::key::FixedKey;
+use super::node::{LeafNode, Node, TreeHeader};
+
+/// A page pinned and latched by the current thread. `release` drops the latch
+/// before the pin and reports a failed write-back; dropping the page without
+/// releasing it, e.g. on an error path, ignores that failure.
+pub(super) struct LatchedPage<G> {
+    pool: BufferPoolInstance,
+    page_id: PageId,
+    guard: Option<G>,
+}
+
+pub(super) type SharedPage = LatchedPage<PageReadGuard>;
+pub(super) type ExclusivePage = LatchedPage<PageWriteGuard>;
+
+impl<G: PageLatch> LatchedPage<G> {
+    pub fn page_id(&self) -> PageId {
+        self.page_id
+    }
+
+    pub fn read_node<K: FixedKey>(&self) -> io::Result<Node<K>> {
+        self.read(Node::read)
+    }
+
+    pub fn read_leaf<K: FixedKey>(&self) -> io::Result<LeafNode<K>> {
+        match self.read_node()? {
+            Node::Leaf(leaf) => Ok(leaf),
+            Node::Internal(_) => Err(io::Error::new(io::ErrorKind::InvalidData, "expected a leaf node")),
+        }
+    }
+
+    pub fn read_header(&self) -> io::Result<TreeHeader> {
+        self.read(TreeHeader::read)
+    }
+
+    pub fn release(mut self) -> io::Result<()> {
+        self.guard = None;
+        self.pool.unpin_page(self.page_id, false)
+    }
+
+    fn read<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        self.guard.as_ref().unwrap().read(f)
+    }
+}
+
+impl SharedPage {
+    pub fn latch(pool: &BufferPoolInstance, page_id: PageId) -> io::Result<Self> {
+        let mut pool = pool.clone();
+        let page = pool.fetch_page(page_id)?.ok_or_else(no_free_frame)?;
+        let guard = page.rlatch();
+        Ok(LatchedPage { pool, page_id, guard: Some(guard) })
+    }
+
+    /// Returns `None` instead of waiting while another thread latches the page
+    /// exclusively.
+    pub fn try_latch(pool: &BufferPoolInstance, page_id: PageId) -> io::Result<Option<Self>> {
+        let mut pool = pool.clone();
+        let page = pool.fetch_page(page_id)?.ok_or_else(no_free_frame)?;
+        match page.try_rlatch() {
+            Some(guard) => Ok(Some(LatchedPage { pool, page_id, guard: Some(guard) })),
+            None => pool.unpin_page(page_id, false).map(|_| None),
+        }
+    }
+}
+
+impl ExclusivePage {
+    pub fn latch(pool: &BufferPoolInstance, page_id: PageId) -> io::Result<Self> {
+        let mut pool = pool.clone();
+        let mut page = pool.fetch_page(page_id)?.ok_or_else(no_free_frame)?;
+        let guard = page.wlatch();
+        Ok(LatchedPage { pool, page_id, guard: Some(guard) })
+    }
+
+    pub fn write_node<K: FixedKey>(&mut self, node: &Node<K>) -> io::Result<()> {
+        self.write(|data| node.write(data))
+    }
+
+    pub fn write_header(&mut self, header: &TreeHeader) -> io::Result<()> {
+        self.write(|data| header.write(data))
+    }
+
+    /// Zeroes the page of a node that was merged away and hands its id back
+    /// to the page allocator. The node is unreachable once its parent is
+    /// updated, so other threads only pin it until they see it is latched or
+    /// until they get to unpin it after releasing their latch.
+    pub fn free(mut self) -> io::Result<()> {
+        self.write(|data| data.fill(0));
+        let mut pool = self.pool.clone();
+        let page_id = self.page_id;
+        self.release()?;
+        if pool.deallocate_page_when_released(page_id) { Ok(()) } else { Err(page_not_deallocated(page_id)) }
+    }
+
+    fn write<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        self.guard.as_mut().unwrap().write(f)
+    }
+}
+
+impl<G> Drop for LatchedPage<G> {
+    fn drop(&mut self) {
+        if self.guard.take().is_some() {
+            let _ = self.pool.unpin_page(self.page_id, false);
+        }
+    }
+}
@@ -0,0 +1,956 @@
+use std::io;
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
+use std::vec;
+
+use crate::buffer::buffer_pool::{no_free_frame, page_not_deallocated};
+use crate::buffer::buffer_pool_instance::BufferPoolInstance;
+use crate::buffer::page::Page;
+use crate::buffer::types::PageId;
+use crate::storage::table_heap::RecordId;
+use crate::types::thread;
+
+use self::key::FixedKey;
+use self::latch::{ExclusivePage, SharedPage};
+use self::node::{internal_capacity, leaf_capacity, InternalNode, LeafNode, Node, TreeHeader};
+
+pub(crate) mod key;
+mod latch;
+mod node;
+
+/// A B+Tree index from unique fixed-width keys to record ids, stored in pages
+/// of the buffer pool. The tree is addressed by its header page, which keeps
+/// the id of the current root. The leaves are chained in key order for range
+/// scans.
+///
+/// Concurrent access uses latch crabbing over the page latches, with the
+/// header page latch guarding the root. Readers hold at most two latches on
+/// the way down. Writers first try to latch only the leaf exclusively, and if
+/// the leaf could split or merge, descend again latching exclusively and keep
+/// only the ancestors that the change could reach.
+#[derive(Clone)]
+pub struct BPlusTree<K> {
+    pool: BufferPoolInstance,
+    header_page_id: PageId,
+    leaf_max_size: usize,
+    internal_max_size: usize,
+    _key: PhantomData<K>,
+}
+
+#[derive(Clone, Copy)]
+enum Operation {
+    Insert,
+    Remove,
+}
+
+/// The pages that a writer keeps latched above the leaf: the header page while
+/// the root may change, and the internal nodes from the topmost one that the
+/// change can reach, with the index of the child that was followed.
+struct WritePath<K> {
+    header: Option<ExclusivePage>,
+    ancestors: Vec<(ExclusivePage, InternalNode<K>, usize)>,
+}
+
+impl<K> WritePath<K> {
+    fn set_root_page_id(&mut self, root_page_id: Option<PageId>) -> io::Result<()> {
+        let page = self
+            .header
+            .as_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "the root changes without the header page latched"))?;
+        let mut header = page.read_header()?;
+        header.root_page_id = root_page_id;
+        page.write_header(&header)
+    }
+
+    /// The number of new nodes an insert creates above a splitting leaf: the
+    /// siblings of the full ancestors, and a new root if the root splits.
+    fn splits(&self, internal_max_size: usize) -> usize {
+        let full = self
+            .ancestors
+            .iter()
+            .rev()
+            .take_while(|(_, internal, _)| internal.children.len() >= internal_max_size)
+            .count();
+        if full == self.ancestors.len() { full + 1 } else { full }
+    }
+
+    fn release(&mut self) -> io::Result<()> {
+        if let Some(header) = self.header.take() {
+            header.release()?;
+        }
+        self.ancestors.drain(..).try_for_each(|(page, _, _)| page.release())
+    }
+}
+
+type LatchedLeaf<K> = (ExclusivePage, LeafNode<K>);
+
+impl<K: FixedKey> BPlusTree<K> {
+    pub fn create(pool: BufferPoolInstance) -> io::Result<Self> {
+        BPlusTree::create_with_max_sizes(pool, leaf_capacity::<K>(), internal_capacity::<K>())
+    }
+
+    /// Creates a tree whose leaves hold at most `leaf_max_size` entries and
+    /// whose internal nodes hold at most `internal_max_size` children.
+    pub fn create_with_max_sizes(
+        mut pool: BufferPoolInstance,
+        leaf_max_size: usize,
+        internal_max_size: usize,
+    ) -> io::Result<Self> {
+        if !(2..=leaf_capacity::<K>()).contains(&leaf_max_size)
+            || !(3..=internal_capacity::<K>()).contains(&internal_max_size)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid node sizes {} and {}", leaf_max_size, internal_max_size),
+            ));
+        }
+
+        let mut page = pool.new_page()?.ok_or_else(no_free_frame)?;
+        let header_page_id = page.get_page_id().unwrap();
+        let header = TreeHeader { root_page_id: None, leaf_max_size, internal_max_size };
+        let written = page.write_data(|data| header.write(data));
+        pool.unpin_page(header_page_id, true)?;
+        written?;
+
+        Ok(BPlusTree { pool, header_page_id, leaf_max_size, internal_max_size, _key: PhantomData })
+    }
+
+    pub fn open(pool: BufferPoolInstance, header_page_id: PageId) -> io::Result<Self> {
+        let page = SharedPage::latch(&pool, header_page_id)?;
+        let header = page.read_header()?;
+        page.release()?;
+
+        Ok(BPlusTree {
+            pool,
+            header_page_id,
+            leaf_max_size: header.leaf_max_size,
+            internal_max_size: header.internal_max_size,
+            _key: PhantomData,
+        })
+    }
+
+    pub fn header_page_id(&self) -> PageId {
+        self.header_page_id
+    }
+
+    pub fn get(&mut self, key: &K) -> io::Result<Option<RecordId>> {
+        match self.find_leaf_shared(Some(key))? {
+            Some((page, leaf)) => {
+                page.release()?;
+                Ok(leaf.position(key).ok().map(|index| leaf.entries[index].1))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Inserts the key, splitting the nodes that overflow. Returns `false` if
+    /// the key is already present.
+    pub fn insert(&mut self, key: K, rid: RecordId) -> io::Result<bool> {
+        if let Some((mut page, mut leaf, _)) = self.find_leaf_exclusive(&key)? {
+            let position = leaf.position(&key);
+            if position.is_ok() || self.leaf_is_safe(&leaf, false, Operation::Insert) {
+                if let Err(index) = position {
+                    leaf.entries.insert(index, (key, rid));
+                    page.write_node(&Node::Leaf(leaf))?;
+                }
+                page.release()?;
+                return Ok(position.is_err());
+            }
+            page.release()?;
+        }
+
+        let (mut path, found) = self.find_leaf_for_write(&key, Operation::Insert)?;
+        let (mut page, mut leaf) = match found {
+            Some(found) => found,
+            None => {
+                let root = Node::Leaf(LeafNode { next_page_id: None, entries: vec![(key, rid)] });
+                let root_page_id = self.new_node(&root)?;
+                path.set_root_page_id(Some(root_page_id))?;
+                path.release()?;
+                return Ok(true);
+            }
+        };
+
+        match leaf.position(&key) {
+            Ok(_) => {
+                page.release()?;
+                path.release()?;
+                return Ok(false);
+            }
+            Err(index) => leaf.entries.insert(index, (key, rid)),
+        }
+        if leaf.entries.len() <= self.leaf_max_size {
+            page.write_node(&Node::Leaf(leaf))?;
+            page.release()?;
+            path.release()?;
+            return Ok(true);
+        }
+
+        // Every page the split needs is taken before a node is written, so a
+        // full buffer pool leaves the tree as it was.
+        let mut reserved = match self.reserve_pages(1 + path.splits(self.internal_max_size)) {
+            Ok(reserved) => reserved,
+            Err(e) => {
+                page.release()?;
+                path.release()?;
+                return Err(e);
+            }
+        };
+        let result = self.split_leaf(path, page, leaf, &mut reserved);
+        if result.is_err() {
+            self.free_reserved(reserved)?;
+        }
+        result.map(|_| true)
+    }
+
+    fn split_leaf(
+        &mut self,
+        path: WritePath<K>,
+        mut page: ExclusivePage,
+        mut leaf: LeafNode<K>,
+        reserved: &mut Vec<Page>,
+    ) -> io::Result<()> {
+        let right_entries = leaf.entries.split_off(leaf.entries.len() / 2);
+        let separator = right_entries[0].0;
+        let right = Node::Leaf(LeafNode { next_page_id: leaf.next_page_id, entries: right_entries });
+        let right_id = self.write_new_node(reserved.pop().unwrap(), &right)?;
+        leaf.next_page_id = Some(right_id);
+        page.write_node(&Node::Leaf(leaf))?;
+
+        let left_id = page.page_id();
+        page.release()?;
+        self.insert_into_parent(path, left_id, separator, right_id, reserved)
+    }
+
+    /// Removes the key, merging or redistributing the nodes that underflow.
+    /// Returns `false` if the key is not present.
+    pub fn remove(&mut self, key: &K) -> io::Result<bool> {
+        if let Some((mut page, mut leaf, is_root)) = self.find_leaf_exclusive(key)? {
+            let position = leaf.position(key);
+            if position.is_err() || self.leaf_is_safe(&leaf, is_root, Operation::Remove) {
+                if let Ok(index) = position {
+                    leaf.entries.remove(index);
+                    page.write_node(&Node::Leaf(leaf))?;
+                }
+                page.release()?;
+                return Ok(position.is_ok());
+            }
+            page.release()?;
+        }
+
+        let (mut path, found) = self.find_leaf_for_write(key, Operation::Remove)?;
+        let (mut page, mut leaf) = match found {
+            Some(found) => found,
+            None => {
+                path.release()?;
+                return Ok(false);
+            }
+        };
+
+        match leaf.position(key) {
+            Ok(index) => leaf.entries.remove(index),
+            Err(_) => {
+                page.release()?;
+                path.release()?;
+                return Ok(false);
+            }
+        };
+
+        let is_root = path.header.is_some() && path.ancestors.is_empty();
+        if is_root && leaf.entries.is_empty() {
+            page.free()?;
+            path.set_root_page_id(None)?;
+        } else if path.ancestors.is_empty() || leaf.entries.len() >= self.leaf_min_size() {
+            page.write_node(&Node::Leaf(leaf))?;
+            page.release()?;
+        } else {
+            return self.rebalance(path, page, Node::Leaf(leaf)).map(|_| true);
+        }
+        path.release()?;
+        Ok(true)
+    }
+
+    /// Iterates over the entries with keys in the range, in key order.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> BPlusTreeIter<K> {
+        BPlusTreeIter {
+            tree: self.clone(),
+            entries: vec![].into_iter(),
+            from: range.start_bound().cloned(),
+            end: range.end_bound().cloned(),
+            done: false,
+        }
+    }
+
+    pub fn iter(&self) -> BPlusTreeIter<K> {
+        self.range(..)
+    }
+
+    /// Descends to the leaf that may contain the key, or to the leftmost leaf
+    /// without a key, releasing every latch once the next one is taken.
+    /// Returns `None` if the tree is empty.
+    fn find_leaf_shared(&mut self, key: Option<&K>) -> io::Result<Option<(SharedPage, LeafNode<K>)>> {
+        let mut parent = SharedPage::latch(&self.pool, self.header_page_id)?;
+        let mut page_id = match parent.read_header()?.root_page_id {
+            Some(page_id) => page_id,
+            None => return parent.release().map(|_| None),
+        };
+
+        loop {
+            let page = SharedPage::latch(&self.pool, page_id)?;
+            parent.release()?;
+            match page.read_node()? {
+                Node::Leaf(leaf) => return Ok(Some((page, leaf))),
+                Node::Internal(internal) => {
+                    page_id = internal.children[key.map_or(0, |key| internal.child_index(key))];
+                    parent = page;
+                }
+            }
+        }
+    }
+
+    /// Descends like `find_leaf_shared`, but latches the leaf exclusively.
+    /// Also returns whether the leaf is the root.
+    fn find_leaf_exclusive(&mut self, key: &K) -> io::Result<Option<(ExclusivePage, LeafNode<K>, bool)>> {
+        let mut parent = SharedPage::latch(&self.pool, self.header_page_id)?;
+        let mut page_id = match parent.read_header()?.root_page_id {
+            Some(page_id) => page_id,
+            None => return parent.release().map(|_| None),
+        };
+        let mut is_root = true;
+
+        loop {
+            let page = SharedPage::latch(&self.pool, page_id)?;
+            match page.read_node()? {
+                Node::Leaf(_) => {
+                    // A leaf only moves in the tree while its parent is latched
+                    // exclusively, so it can be latched again under the parent.
+                    page.release()?;
+                    let page = ExclusivePage::latch(&self.pool, page_id)?;
+                    parent.release()?;
+                    let leaf = page.read_leaf()?;
+                    return Ok(Some((page, leaf, is_root)));
+                }
+                Node::Internal(internal) => {
+                    parent.release()?;
+                    page_id = internal.children[internal.child_index(key)];
+                    parent = page;
+                    is_root = false;
+                }
+            }
+        }
+    }
+
+    /// Descends latching exclusively and releases the ancestors of every node
+    /// that the operation cannot split or merge. The leaf is `None` if the tree
+    /// is empty, in which case the header page stays latched.
+    fn find_leaf_for_write(
+        &mut self,
+        key: &K,
+        operation: Operation,
+    ) -> io::Result<(WritePath<K>, Option<LatchedLeaf<K>>)> {
+        let header = ExclusivePage::latch(&self.pool, self.header_page_id)?;
+        let mut page_id = header.read_header()?.root_page_id;
+        let mut path = WritePath { header: Some(header), ancestors: vec![] };
+        let mut is_root = true;
+
+        while let Some(current) = page_id {
+            let page = ExclusivePage::latch(&self.pool, current)?;
+            match page.read_node()? {
+                Node::Leaf(leaf) => {
+                    if self.leaf_is_safe(&leaf, is_root, operation) {
+                        path.release()?;
+                    }
+                    return Ok((path, Some((page, leaf))));
+                }
+                Node::Internal(internal) => {
+                    if self.internal_is_safe(&internal, is_root, operation) {
+                        path.release()?;
+                    }
+                    let index = internal.child_index(key);
+                    page_id = Some(internal.children[index]);
+                    path.ancestors.push((page, internal, index));
+                    is_root = false;
+                }
+            }
+        }
+        Ok((path, None))
+    }
+
+    fn insert_into_parent(
+        &mut self,
+        mut path: WritePath<K>,
+        mut left_id: PageId,
+        mut key: K,
+        mut right_id: PageId,
+        reserved: &mut Vec<Page>,
+    ) -> io::Result<()> {
+        while let Some((mut page, mut parent, index)) = path.ancestors.pop() {
+            parent.keys.insert(index, key);
+            parent.children.insert(index + 1, right_id);
+            if parent.children.len() <= self.internal_max_size {
+                page.write_node(&Node::Internal(parent))?;
+                page.release()?;
+                return path.release();
+            }
+
+            let middle = parent.keys.len() / 2;
+            let mut right_keys = parent.keys.split_off(middle);
+            let separator = right_keys.remove(0);
+            let right_children = parent.children.split_off(middle + 1);
+            let right = Node::Internal(InternalNode { keys: right_keys, children: right_children });
+            let new_right_id = self.write_new_node(reserved.pop().unwrap(), &right)?;
+            page.write_node(&Node::Internal(parent))?;
+
+            left_id = page.page_id();
+            page.release()?;
+            key = separator;
+            right_id = new_right_id;
+        }
+
+        let root = Node::Internal(InternalNode { keys: vec![key], children: vec![left_id, right_id] });
+        let root_page_id = self.write_new_node(reserved.pop().unwrap(), &root)?;
+        path.set_root_page_id(Some(root_page_id))?;
+        path.release()
+    }
+
+    /// Fixes an underflowing node by borrowing an entry from a sibling, or by
+    /// merging it with the sibling and continuing with the parent. The sibling
+    /// is latched under the exclusive latch of the parent.
+    fn rebalance(&mut self, mut path: WritePath<K>, mut page: ExclusivePage, mut node: Node<K>) -> io::Result<()> {
+        while let Some((mut parent_page, mut parent, index)) = path.ancestors.pop() {
+            let separator = if index > 0 { index - 1 } else { index };
+            let sibling_page =
+                ExclusivePage::latch(&self.pool, parent.children[if index > 0 { index - 1 } else { index + 1 }])?;
+            let sibling = sibling_page.read_node()?;
+            let can_lend = match &sibling {
+                Node::Leaf(leaf) => leaf.entries.len() > self.leaf_min_size(),
+                Node::Internal(internal) => internal.children.len() > self.internal_min_size(),
+            };
+            let ((mut left_page, mut left), (right_page, mut right)) = if index > 0 {
+                ((sibling_page, sibling), (page, node))
+            } else {
+                ((page, node), (sibling_page, sibling))
+            };
+
+            if can_lend {
+                match (&mut left, &mut right) {
+                    (Node::Leaf(left), Node::Leaf(right)) => {
+                        if index > 0 {
+                            right.entries.insert(0, left.entries.pop().unwrap());
+                        } else {
+                            left.entries.push(right.entries.remove(0));
+                        }
+                        parent.keys[separator] = right.entries[0].0;
+                    }
+                    (Node::Internal(left), Node::Internal(right)) => {
+                        if index > 0 {
+                            right.keys.insert(0, parent.keys[separator]);
+                            right.children.insert(0, left.children.pop().unwrap());
+                            parent.keys[separator] = left.keys.pop().unwrap();
+                        } else {
+                            left.keys.push(parent.keys[separator]);
+                            left.children.push(right.children.remove(0));
+                            parent.keys[separator] = right.keys.remove(0);
+                        }
+                    }
+                    _ => return Err(mismatched_siblings()),
+                }
+
+                let mut right_page = right_page;
+                left_page.write_node(&left)?;
+                right_page.write_node(&right)?;
+                parent_page.write_node(&Node::Internal(parent))?;
+                left_page.release()?;
+                right_page.release()?;
+                parent_page.release()?;
+                return path.release();
+            }
+
+            match (&mut left, right) {
+                (Node::Leaf(left), Node::Leaf(right)) => {
+                    left.entries.extend(right.entries);
+                    left.next_page_id = right.next_page_id;
+                }
+                (Node::Internal(left), Node::Internal(right)) => {
+                    left.keys.push(parent.keys[separator]);
+                    left.keys.extend(right.keys);
+                    left.children.extend(right.children);
+                }
+                _ => return Err(mismatched_siblings()),
+            }
+            left_page.write_node(&left)?;
+            left_page.release()?;
+            right_page.free()?;
+            parent.keys.remove(separator);
+            parent.children.remove(separator + 1);
+
+            let is_root = path.header.is_some() && path.ancestors.is_empty();
+            if is_root && parent.children.len() == 1 {
+                path.set_root_page_id(Some(parent.children[0]))?;
+                parent_page.free()?;
+                return path.release();
+            }
+            if path.ancestors.is_empty() || parent.children.len() >= self.internal_min_size() {
+                parent_page.write_node(&Node::Internal(parent))?;
+                parent_page.release()?;
+                return path.release();
+            }
+
+            page = parent_page;
+            node = Node::Internal(parent);
+        }
+
+        page.release()?;
+        path.release()
+    }
+
+    /// Whether the operation leaves the leaf within its size limits.
+    fn leaf_is_safe(&self, leaf: &LeafNode<K>, is_root: bool, operation: Operation) -> bool {
+        match operation {
+            Operation::Insert => leaf.entries.len() < self.leaf_max_size,
+            Operation::Remove => leaf.entries.len() > if is_root { 1 } else { self.leaf_min_size() },
+        }
+    }
+
+    /// Whether a split or merge of a child leaves the node within its size
+    /// limits.
+    fn internal_is_safe(&self, internal: &InternalNode<K>, is_root: bool, operation: Operation) -> bool {
+        match operation {
+            Operation::Insert => internal.children.len() < self.internal_max_size,
+            Operation::Remove => internal.children.len() > if is_root { 2 } else { self.internal_min_size() },
+        }
+    }
+
+    /// The fewest entries a leaf other than the root may hold.
+    fn leaf_min_size(&self) -> usize {
+        self.leaf_max_size / 2
+    }
+
+    /// The fewest children an internal node other than the root may hold.
+    fn internal_min_size(&self) -> usize {
+        (self.internal_max_size + 1) / 2
+    }
+
+    /// Writes a node into a new page.
+    fn new_node(&mut self, node: &Node<K>) -> io::Result<PageId> {
+        let page = self.reserve_pages(1)?.pop().unwrap();
+        self.write_new_node(page, node)
+    }
+
+    /// Takes `count` new pages and keeps them pinned, or none of them if the
+    /// buffer pool runs out of frames.
+    fn reserve_pages(&mut self, count: usize) -> io::Result<Vec<Page>> {
+        let mut pages = Vec::with_capacity(count);
+        for _ in 0..count {
+            match self.pool.new_page().and_then(|page| page.ok_or_else(no_free_frame)) {
+                Ok(page) => pages.push(page),
+                Err(e) => {
+                    self.free_reserved(pages)?;
+                    return Err(e);
+                }
+            }
+        }
+        Ok(pages)
+    }
+
+    fn free_reserved(&mut self, pages: Vec<Page>) -> io::Result<()> {
+        for page in pages {
+            let page_id = page.get_page_id().unwrap();
+            self.pool.unpin_page(page_id, false)?;
+            if !self.pool.deallocate_page(page_id) {
+                return Err(page_not_deallocated(page_id));
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a node into a reserved page and unpins it. The page is not
+    /// reachable before its id is stored in a latched page, so it is not
+    /// latched itself.
+    fn write_new_node(&mut self, mut page: Page, node: &Node<K>) -> io::Result<PageId> {
+        let page_id = page.get_page_id().unwrap();
+        let written = page.write_data(|data| node.write(data));
+        self.pool.unpin_page(page_id, true)?;
+        if written.is_err() && !self.pool.deallocate_page(page_id) {
+            return Err(page_not_deallocated(page_id));
+        }
+        written.map(|_| page_id)
+    }
+}
+
+/// Walks the leaf chain. The entries of one leaf are copied out, so no latch is
+/// held between calls to `next`; the following leaf is latched before the
+/// current one is released. Scans latch leaves left to right while writers may
+/// latch a left sibling, so a scan does not wait for the next leaf: it looks up
+/// its position from the root again instead.
+pub struct BPlusTreeIter<K> {
+    tree: BPlusTree<K>,
+    entries: vec::IntoIter<(K, RecordId)>,
+    /// The start of the range, then the last returned key.
+    from: Bound<K>,
+    end: Bound<K>,
+    done: bool,
+}
+
+impl<K: FixedKey> BPlusTreeIter<K> {
+    fn refill(&mut self) -> io::Result<()> {
+        'seek: loop {
+            let from = match self.from {
+                Bound::Included(key) | Bound::Excluded(key) => Some(key),
+                Bound::Unbounded => None,
+            };
+            let (mut page, mut leaf) = match self.tree.find_leaf_shared(from.as_ref())? {
+                Some(found) => found,
+                None => {
+                    self.done = true;
+                    return Ok(());
+                }
+            };
+
+            loop {
+                let next_page_id = leaf.next_page_id;
+                let entries: Vec<_> =
+                    leaf.entries.into_iter().filter(|(key, _)| in_start_bound(self.from.as_ref(), key)).collect();
+                if !entries.is_empty() || next_page_id.is_none() {
+                    self.entries = entries.into_iter();
+                    self.done = next_page_id.is_none();
+                    return page.release();
+                }
+
+                match SharedPage::try_latch(&self.tree.pool, next_page_id.unwrap())? {
+                    Some(next_page) => {
+                        page.release()?;
+                        leaf = next_page.read_leaf()?;
+                        page = next_page;
+                    }
+                    None => {
+                        page.release()?;
+                        thread::yield_now();
+                        continue 'seek;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<K: FixedKey> Iterator for BPlusTreeIter<K> {
+    type Item = io::Result<(K, RecordId)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((key, rid)) = self.entries.next() {
+                let in_range = match &self.end {
+                    Bound::Included(end) => key <= *end,
+                    Bound::Excluded(end) => key < *end,
+                    Bound::Unbounded => true,
+                };
+                if !in_range {
+                    self.entries = vec![].into_iter();
+                    self.done = true;
+                    return None;
+                }
+                self.from = Bound::Excluded(key);
+                return Some(Ok((key, rid)));
+            }
+
+            if self.done {
+                return None;
+            }
+            if let Err(e) = self.refill() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+fn in_start_bound<K: Ord>(start: Bound<&K>, key: &K) -> bool {
+    match start {
+        Bound::Included(start) => key >= start,
+        Bound::Excluded(start) => key > start,
+        Bound::Unbounded => true,
+    }
+}
+
+fn mismatched_siblings() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "sibling nodes have different kinds")
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::io;
+    use std::ops::Bound;
+
+    use crate::buffer::buffer_pool_instance::BufferPoolInstance;
+    use crate::buffer::disk_manager::DiskManager;
+    use crate::buffer::test_utils::TempFile;
+    use crate::storage::table_heap::RecordId;
+    use crate::types::{check_random, thread, thread_rng, Rng};
+
+    use super::latch::SharedPage;
+    use super::BPlusTree;
+
+    fn rid(key: u32) -> RecordId {
+        RecordId { page_id: key as usize, slot_id: (key % 100) as u16 }
+    }
+
+    fn small_tree(file: &TempFile) -> io::Result<BPlusTree<u32>> {
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 16);
+        BPlusTree::create_with_max_sizes(pool, 4, 4)
+    }
+
+    fn keys(tree: &BPlusTree<u32>) -> io::Result<Vec<u32>> {
+        tree.iter().map(|entry| entry.map(|(key, _)| key)).collect()
+    }
+
+    #[test]
+    fn should_insert_and_get() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let mut tree = small_tree(&file)?;
+        assert_eq!(tree.get(&1)?, None);
+
+        let mut inserted: Vec<u32> = (0..500).map(|i| i * 7 % 500).collect();
+        inserted.dedup();
+        for key in &inserted {
+            assert!(tree.insert(*key, rid(*key))?);
+        }
+        assert!(!tree.insert(7, rid(0))?);
+
+        for key in 0..500 {
+            assert_eq!(tree.get(&key)?, Some(rid(key)));
+        }
+        assert_eq!(tree.get(&500)?, None);
+        assert_eq!(keys(&tree)?, (0..500).collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_scan_ranges() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let mut tree = small_tree(&file)?;
+        for key in (0..100).map(|i| i * 2) {
+            tree.insert(key, rid(key))?;
+        }
+
+        let range = |range: (Bound<u32>, Bound<u32>)| -> io::Result<Vec<u32>> {
+            tree.range(range).map(|entry| entry.map(|(key, _)| key)).collect()
+        };
+        assert_eq!(range((Bound::Included(10), Bound::Excluded(20)))?, vec![10, 12, 14, 16, 18]);
+        assert_eq!(range((Bound::Excluded(9), Bound::Included(15)))?, vec![10, 12, 14]);
+        assert_eq!(range((Bound::Excluded(194), Bound::Unbounded))?, vec![196, 198]);
+        assert_eq!(range((Bound::Included(300), Bound::Unbounded))?, vec![]);
+        assert_eq!(tree.range(..4).collect::<io::Result<Vec<_>>>()?, vec![(0, rid(0)), (2, rid(2))]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_keep_keys_when_pool_fills_during_split() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let mut pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 5);
+        let mut tree = BPlusTree::create_with_max_sizes(pool.clone(), 4, 4)?;
+        for key in 0..4 {
+            tree.insert(key, rid(key))?;
+        }
+
+        // The header and the root leaf stay resident, which leaves one frame
+        // for the two nodes the split of the root needs.
+        let pinned: Vec<_> = (0..2).map(|_| pool.new_page().unwrap().unwrap().get_page_id().unwrap()).collect();
+        assert_eq!(tree.insert(4, rid(4)).unwrap_err().kind(), io::ErrorKind::OutOfMemory);
+        for key in 0..4 {
+            assert_eq!(tree.get(&key)?, Some(rid(key)));
+        }
+        assert_eq!(tree.get(&4)?, None);
+
+        for page_id in pinned {
+            pool.unpin_page(page_id, false)?;
+        }
+        assert!(tree.insert(4, rid(4))?);
+        for key in 0..5 {
+            assert_eq!(tree.get(&key)?, Some(rid(key)));
+        }
+        assert_eq!(keys(&tree)?, (0..5).collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_remove_with_merge_and_redistribute() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let mut tree = small_tree(&file)?;
+        for key in 0..300 {
+            tree.insert(key, rid(key))?;
+        }
+
+        for key in (0..300).filter(|key| key % 3 != 0) {
+            assert!(tree.remove(&key)?);
+        }
+        assert!(!tree.remove(&1)?);
+        assert_eq!(keys(&tree)?, (0..300).filter(|key| key % 3 == 0).collect::<Vec<_>>());
+
+        for key in (0..300).filter(|key| key % 3 == 0).rev() {
+            assert!(tree.remove(&key)?);
+            assert_eq!(tree.get(&key)?, None);
+        }
+        assert_eq!(keys(&tree)?, vec![]);
+        let header = SharedPage::latch(&tree.pool, tree.header_page_id())?;
+        assert_eq!(header.read_header()?.root_page_id, None);
+        header.release()?;
+
+        assert!(tree.insert(5, rid(5))?);
+        assert_eq!(keys(&tree)?, vec![5]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_reuse_pages_of_merged_nodes() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let mut tree = small_tree(&file)?;
+        for key in 0..100 {
+            tree.insert(key, rid(key))?;
+        }
+        let mut pool = tree.pool.clone();
+        let next_page_id = pool.new_page()?.unwrap().get_page_id().unwrap();
+        pool.unpin_page(next_page_id, false)?;
+
+        for key in 0..90 {
+            assert!(tree.remove(&key)?);
+        }
+
+        let page_id = pool.new_page()?.unwrap().get_page_id().unwrap();
+        assert!(page_id < next_page_id);
+        pool.unpin_page(page_id, false)?;
+        assert_eq!(keys(&tree)?, (90..100).collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_match_btree_map() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let mut tree = small_tree(&file)?;
+        let mut expected = BTreeMap::new();
+        let mut rng = thread_rng();
+
+        for _ in 0..3000 {
+            let key = rng.gen_range(0..200);
+            if rng.gen_bool(0.6) {
+                assert_eq!(tree.insert(key, rid(key))?, expected.insert(key, rid(key)).is_none());
+            } else {
+                assert_eq!(tree.remove(&key)?, expected.remove(&key).is_some());
+            }
+        }
+
+        let entries = tree.iter().collect::<io::Result<Vec<_>>>()?;
+        assert_eq!(entries, expected.into_iter().collect::<Vec<_>>());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_persist_root_in_header_page() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 16);
+        let mut tree = BPlusTree::create_with_max_sizes(pool.clone(), 4, 4)?;
+        for key in 0..100 {
+            tree.insert(key, rid(key))?;
+        }
+
+        let mut reopened = BPlusTree::<u32>::open(pool, tree.header_page_id())?;
+        assert_eq!(reopened.get(&42)?, Some(rid(42)));
+        assert_eq!(keys(&reopened)?.len(), 100);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_fill_pages_with_default_sizes() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 16);
+        let mut tree = BPlusTree::<[u8; 16]>::create(pool)?;
+
+        let key = |i: u32| {
+            let mut key = [0; 16];
+            key[..4].copy_from_slice(&i.to_be_bytes());
+            key
+        };
+        for i in 0..5000 {
+            tree.insert(key(i), rid(i))?;
+        }
+        for i in (0..5000).step_by(2) {
+            tree.remove(&key(i))?;
+        }
+
+        assert_eq!(tree.get(&key(4321))?, Some(rid(4321)));
+        assert_eq!(tree.iter().count(), 2500);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_reject_invalid_node_sizes() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 16);
+
+        let error = BPlusTree::<u32>::create_with_max_sizes(pool, 1, 4).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_work_concurrently() {
+        check_random(
+            || {
+                let file = TempFile::new().unwrap();
+                let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path()).unwrap(), 64);
+                let mut tree = BPlusTree::<u32>::create_with_max_sizes(pool, 3, 3).unwrap();
+
+                // Keys divisible by 4 stay in the tree, the other keys belong
+                // to one writer each.
+                let stable: Vec<u32> = (0..40).filter(|key| key % 4 == 0).collect();
+                for key in &stable {
+                    tree.insert(*key, rid(*key)).unwrap();
+                }
+
+                let writers: Vec<_> = (1..4)
+                    .map(|writer| {
+                        let mut tree = tree.clone();
+                        thread::spawn(move || {
+                            let keys: Vec<u32> = (0..40).filter(|key| key % 4 == writer).collect();
+                            for key in &keys {
+                                assert!(tree.insert(*key, rid(*key)).unwrap());
+                            }
+                            for key in keys.iter().step_by(2) {
+                                assert!(tree.remove(key).unwrap());
+                            }
+                        })
+                    })
+                    .collect();
+
+                let mut reader = tree.clone();
+                let scanner = thread::spawn(move || {
+                    for _ in 0..3 {
+                        let keys: Vec<u32> = reader.iter().map(|entry| entry.unwrap().0).collect();
+                        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+                        assert!(stable.iter().all(|key| keys.contains(key)));
+                        assert_eq!(reader.get(&8).unwrap(), Some(rid(8)));
+                    }
+                });
+
+                for writer in writers {
+                    writer.join().unwrap();
+                }
+                scanner.join().unwrap();
+
+                let expected: Vec<u32> = (0..40).filter(|key| key % 4 == 0 || key / 4 % 2 == 1).collect();
+                assert_eq!(keys(&tree).unwrap(), expected);
+            },
+            100,
+        );
+    }
+}
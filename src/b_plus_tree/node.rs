@@ -0,0 +1,242 @@
+use std::io;
+
+use crate::buffer::constants::PAGE_SIZE;
+use crate::buffer::page_view::{PageHeader, PageType, PageView};
+use crate::buffer::types::PageId;
+use crate::storage::table_heap::RecordId;
+
+use super::key::FixedKey;
+
+const NO_PAGE: u64 = u64::MAX;
+const RECORD_ID_SIZE: usize = 10;
+
+const SIZE_OFFSET: usize = PageHeader::SIZE;
+const NEXT_PAGE_ID_OFFSET: usize = SIZE_OFFSET + 2;
+const LEAF_ENTRIES_OFFSET: usize = NEXT_PAGE_ID_OFFSET + 8;
+const INTERNAL_ENTRIES_OFFSET: usize = SIZE_OFFSET + 2;
+
+const ROOT_PAGE_ID_OFFSET: usize = PageHeader::SIZE;
+const LEAF_MAX_SIZE_OFFSET: usize = ROOT_PAGE_ID_OFFSET + 8;
+const INTERNAL_MAX_SIZE_OFFSET: usize = LEAF_MAX_SIZE_OFFSET + 2;
+
+/// The number of entries that fit into a leaf page.
+pub(super) fn leaf_capacity<K: FixedKey>() -> usize {
+    (PAGE_SIZE - LEAF_ENTRIES_OFFSET) / (K::SIZE + RECORD_ID_SIZE)
+}
+
+/// The number of children that fit into an internal page.
+pub(super) fn internal_capacity<K: FixedKey>() -> usize {
+    (PAGE_SIZE - INTERNAL_ENTRIES_OFFSET - 8) / (K::SIZE + 8) + 1
+}
+
+/// The first page of a tree. It stores the root page id, which changes when the
+/// root splits or collapses, and the node sizes the tree was created with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct TreeHeader {
+    pub root_page_id: Option<PageId>,
+    pub leaf_max_size: usize,
+    pub internal_max_size: usize,
+}
+
+impl TreeHeader {
+    pub fn read(data: &[u8]) -> io::Result<Self> {
+        let view = PageView::new(data);
+        if view.read_header()?.page_type != PageType::BPlusTreeHeader {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a b+tree header page"));
+        }
+        Ok(TreeHeader {
+            root_page_id: read_page_id(&view, ROOT_PAGE_ID_OFFSET)?,
+            leaf_max_size: view.read_u16_at(LEAF_MAX_SIZE_OFFSET)? as usize,
+            internal_max_size: view.read_u16_at(INTERNAL_MAX_SIZE_OFFSET)? as usize,
+        })
+    }
+
+    pub fn write(&self, data: &mut [u8]) -> io::Result<()> {
+        let mut view = PageView::new(data);
+        view.write_header(&PageHeader::new(PageType::BPlusTreeHeader, (INTERNAL_MAX_SIZE_OFFSET + 2) as u16))?;
+        write_page_id(&mut view, ROOT_PAGE_ID_OFFSET, self.root_page_id)?;
+        view.write_u16_at(LEAF_MAX_SIZE_OFFSET, self.leaf_max_size as u16)?;
+        view.write_u16_at(INTERNAL_MAX_SIZE_OFFSET, self.internal_max_size as u16)
+    }
+}
+
+/// The sorted entries of a leaf and the next leaf in key order.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct LeafNode<K> {
+    pub next_page_id: Option<PageId>,
+    pub entries: Vec<(K, RecordId)>,
+}
+
+impl<K: FixedKey> LeafNode<K> {
+    /// Returns the index of the key, or the index where it would be inserted.
+    pub fn position(&self, key: &K) -> Result<usize, usize> {
+        self.entries.binary_search_by(|(k, _)| k.cmp(key))
+    }
+}
+
+/// The keys and children of an internal node. `keys[i]` separates
+/// `children[i]` from `children[i + 1]`, which holds the keys greater than or
+/// equal to it.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct InternalNode<K> {
+    pub keys: Vec<K>,
+    pub children: Vec<PageId>,
+}
+
+impl<K: FixedKey> InternalNode<K> {
+    /// Returns the index of the child that may contain the key.
+    pub fn child_index(&self, key: &K) -> usize {
+        self.keys.partition_point(|k| k <= key)
+    }
+}
+
+/// A node of the tree decoded from its page.
+///
+/// A leaf page stores the entry count and the next page id after the header,
+/// followed by the `(key, record id)` entries. An internal page stores the
+/// child count, the first child, and then a `(key, child)` pair for every
+/// other child.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Node<K> {
+    Leaf(LeafNode<K>),
+    Internal(InternalNode<K>),
+}
+
+impl<K: FixedKey> Node<K> {
+    /// The number of entries of a leaf or children of an internal node.
+    pub fn size(&self) -> usize {
+        match self {
+            Node::Leaf(leaf) => leaf.entries.len(),
+            Node::Internal(internal) => internal.children.len(),
+        }
+    }
+
+    pub fn read(data: &[u8]) -> io::Result<Self> {
+        let view = PageView::new(data);
+        let page_type = view.read_header()?.page_type;
+        let size = view.read_u16_at(SIZE_OFFSET)? as usize;
+
+        match page_type {
+            PageType::BPlusTreeLeaf => {
+                let entry_size = K::SIZE + RECORD_ID_SIZE;
+                let entries = (0..size)
+                    .map(|i| {
+                        let offset = LEAF_ENTRIES_OFFSET + i * entry_size;
+                        let key = K::decode(view.read_bytes_at(offset, K::SIZE)?);
+                        let page_id = view.read_u64_at(offset + K::SIZE)? as PageId;
+                        let slot_id = view.read_u16_at(offset + K::SIZE + 8)?;
+                        Ok((key, RecordId { page_id, slot_id }))
+                    })
+                    .collect::<io::Result<_>>()?;
+                Ok(Node::Leaf(LeafNode { next_page_id: read_page_id(&view, NEXT_PAGE_ID_OFFSET)?, entries }))
+            }
+            PageType::BPlusTreeInternal => {
+                let mut keys = Vec::with_capacity(size.saturating_sub(1));
+                let mut children = Vec::with_capacity(size);
+                children.push(view.read_u64_at(INTERNAL_ENTRIES_OFFSET)? as PageId);
+                for i in 1..size {
+                    let offset = INTERNAL_ENTRIES_OFFSET + 8 + (i - 1) * (K::SIZE + 8);
+                    keys.push(K::decode(view.read_bytes_at(offset, K::SIZE)?));
+                    children.push(view.read_u64_at(offset + K::SIZE)? as PageId);
+                }
+                Ok(Node::Internal(InternalNode { keys, children }))
+            }
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "not a b+tree node page")),
+        }
+    }
+
+    pub fn write(&self, data: &mut [u8]) -> io::Result<()> {
+        let mut view = PageView::new(data);
+        let mut key = vec![0; K::SIZE];
+
+        let (page_type, end) = match self {
+            Node::Leaf(leaf) => {
+                write_page_id(&mut view, NEXT_PAGE_ID_OFFSET, leaf.next_page_id)?;
+                let mut offset = LEAF_ENTRIES_OFFSET;
+                for (k, rid) in &leaf.entries {
+                    k.encode(&mut key);
+                    view.write_bytes_at(offset, &key)?;
+                    view.write_u64_at(offset + K::SIZE, rid.page_id as u64)?;
+                    view.write_u16_at(offset + K::SIZE + 8, rid.slot_id)?;
+                    offset += K::SIZE + RECORD_ID_SIZE;
+                }
+                (PageType::BPlusTreeLeaf, offset)
+            }
+            Node::Internal(internal) => {
+                view.write_u64_at(INTERNAL_ENTRIES_OFFSET, internal.children[0] as u64)?;
+                let mut offset = INTERNAL_ENTRIES_OFFSET + 8;
+                for (k, child) in internal.keys.iter().zip(&internal.children[1..]) {
+                    k.encode(&mut key);
+                    view.write_bytes_at(offset, &key)?;
+                    view.write_u64_at(offset + K::SIZE, *child as u64)?;
+                    offset += K::SIZE + 8;
+                }
+                (PageType::BPlusTreeInternal, offset)
+            }
+        };
+
+        view.write_header(&PageHeader::new(page_type, end as u16))?;
+        view.write_u16_at(SIZE_OFFSET, self.size() as u16)
+    }
+}
+
+fn read_page_id<B: AsRef<[u8]>>(view: &PageView<B>, offset: usize) -> io::Result<Option<PageId>> {
+    let page_id = view.read_u64_at(offset)?;
+    Ok(Some(page_id as PageId).filter(|_| page_id != NO_PAGE))
+}
+
+fn write_page_id<B: AsRef<[u8]> + AsMut<[u8]>>(
+    view: &mut PageView<B>,
+    offset: usize,
+    page_id: Option<PageId>,
+) -> io::Result<()> {
+    view.write_u64_at(offset, page_id.map_or(NO_PAGE, |page_id| page_id as u64))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use crate::buffer::constants::PAGE_SIZE;
+    use crate::storage::table_heap::RecordId;
+
+    use super::{internal_capacity, leaf_capacity, InternalNode, LeafNode, Node, TreeHeader};
+
+    #[test]
+    fn should_round_trip_leaf() -> io::Result<()> {
+        let entries = (0..leaf_capacity::<u64>() as u64)
+            .map(|i| (i * 3, RecordId { page_id: i as usize, slot_id: i as u16 }))
+            .collect();
+        let node = Node::Leaf(LeafNode { next_page_id: Some(7), entries });
+        let mut data = [0; PAGE_SIZE];
+
+        node.write(&mut data)?;
+        assert_eq!(Node::<u64>::read(&data)?, node);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_round_trip_internal() -> io::Result<()> {
+        let size = internal_capacity::<u32>();
+        let node = Node::Internal(InternalNode { keys: (1..size as u32).collect(), children: (0..size).collect() });
+        let mut data = [0; PAGE_SIZE];
+
+        node.write(&mut data)?;
+        assert_eq!(Node::<u32>::read(&data)?, node);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_round_trip_header() -> io::Result<()> {
+        let header = TreeHeader { root_page_id: None, leaf_max_size: 4, internal_max_size: 5 };
+        let mut data = [0; PAGE_SIZE];
+
+        header.write(&mut data)?;
+        assert_eq!(TreeHeader::read(&data)?, header);
+        assert!(Node::<u32>::read(&data).is_err());
+
+        Ok(())
+    }
+}
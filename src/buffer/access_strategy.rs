@@ -0,0 +1,70 @@
+use std::collections::VecDeque;
+
+use crate::buffer::types::{FrameId, PageId};
+
+/// Describes how the caller is going to use the pages it requests from a buffer
+/// pool.
+///
+/// `Normal` access goes through the shared replacer. The bulk strategies keep a
+/// small private ring of frames and recycle them once the ring is full, so a
+/// sequential scan or a bulk load does not evict the pages that concurrent
+/// point lookups rely on.
+pub enum AccessStrategy {
+    Normal,
+    /// Recycles only clean ring frames: a page that was modified during the
+    /// scan is left to the shared replacer.
+    BulkRead(Ring),
+    /// Recycles ring frames regardless of their state, writing dirty pages out
+    /// before reuse.
+    BulkWrite(Ring),
+}
+
+impl AccessStrategy {
+    pub fn bulk_read(ring_size: usize) -> Self {
+        AccessStrategy::BulkRead(Ring::new(ring_size))
+    }
+
+    pub fn bulk_write(ring_size: usize) -> Self {
+        AccessStrategy::BulkWrite(Ring::new(ring_size))
+    }
+}
+
+pub struct Ring {
+    capacity: usize,
+    frames: VecDeque<(FrameId, PageId)>,
+}
+
+impl Ring {
+    fn new(capacity: usize) -> Self {
+        Ring { capacity: capacity.max(1), frames: VecDeque::with_capacity(capacity) }
+    }
+
+    /// Returns the oldest frame of the ring once the ring is full, together
+    /// with the page it was loaded with.
+    pub(super) fn next_victim(&mut self) -> Option<(FrameId, PageId)> {
+        if self.frames.len() >= self.capacity { self.frames.pop_front() } else { None }
+    }
+
+    pub(super) fn push(&mut self, frame_id: FrameId, page_id: PageId) {
+        self.frames.push_back((frame_id, page_id))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Ring;
+
+    #[test]
+    fn should_recycle_frames_once_full() {
+        let mut ring = Ring::new(2);
+
+        assert_eq!(ring.next_victim(), None);
+        ring.push(1, 10);
+        assert_eq!(ring.next_victim(), None);
+        ring.push(2, 20);
+
+        assert_eq!(ring.next_victim(), Some((1, 10)));
+        ring.push(1, 30);
+        assert_eq!(ring.next_victim(), Some((2, 20)));
+    }
+}
@@ -1,3 +1,17 @@
+use std::io;
+
 use crate::buffer::types::PageId;
 
 pub(super) type PageIdIncrementFn = Box<dyn Fn(PageId) -> PageId + Send>;
+
+/// The error returned by structures built on the buffer pool when every frame
+/// is pinned and the page they need cannot be brought in.
+pub(crate) fn no_free_frame() -> io::Error {
+    io::Error::new(io::ErrorKind::OutOfMemory, "no free frame in the buffer pool")
+}
+
+/// The error returned when a page that is no longer needed cannot be handed
+/// back to the allocator, because it is still pinned or was freed before.
+pub(crate) fn page_not_deallocated(page_id: PageId) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, format!("page {} is pinned or already deallocated", page_id))
+}
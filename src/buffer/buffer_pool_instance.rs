@@ -1,21 +1,42 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::io;
+use std::panic::Location;
+use std::path::{Path, PathBuf};
 
+use crate::buffer::access_strategy::{AccessStrategy, Ring};
 use crate::buffer::buffer_pool::PageIdIncrementFn;
 use crate::buffer::disk_manager::DiskManager;
 use crate::buffer::lru::LRU;
-use crate::buffer::page::Page;
+use crate::buffer::page::{Page, PageLatch};
 use crate::buffer::types::{FrameId, PageId};
-use crate::types::{Arc, Mutex};
+use crate::buffer::warm_up;
+use crate::types::{thread, Arc, Mutex};
 
 struct UnsafeBufferPoolInstance {
     lru: LRU,
     disk_manager: DiskManager,
     next_page_id: PageId,
     inc_fn: PageIdIncrementFn,
+    /// Deallocated page ids, handed out again before new ones.
+    free_page_ids: BTreeSet<PageId>,
     pages: Vec<Page>,
     free_list: VecDeque<FrameId>,
     page_table: HashMap<PageId, FrameId>,
+    ring_frames: HashSet<FrameId>,
+    pin_tracking: bool,
+    pin_sites: HashMap<PageId, PinSites>,
+    /// The size a waiting `resize` shrinks the pool to. Frames at or above it
+    /// are not handed out to other pages meanwhile.
+    shrink_to: Option<usize>,
+}
+
+/// The call sites of the pins and unpins of a page since it was last unpinned
+/// completely, with how often each was hit, so a page that never gets unpinned
+/// completely does not grow them without bound.
+#[derive(Default)]
+struct PinSites {
+    pins: Vec<(&'static Location<'static>, u32)>,
+    unpins: Vec<(&'static Location<'static>, u32)>,
 }
 
 impl UnsafeBufferPoolInstance {
@@ -23,33 +44,39 @@ impl UnsafeBufferPoolInstance {
         self.page_table.get(&page_id).map(|frame_id| (*frame_id, self.pages[*frame_id].clone()))
     }
 
-    fn flush_page(&self, page_id: PageId) -> io::Result<bool> {
-        if let Some((_frame_id, mut page)) = self.find_page(page_id) {
-            self.write_page(&mut page)?;
-            Ok(true)
-        } else {
-            Ok(false)
-        }
-    }
-
+    /// Writes the page if it is dirty. Only called for frames not in use, so
+    /// that waiting for the latch cannot block the pool.
     fn write_page(&self, page: &mut Page) -> io::Result<()> {
         if page.is_dirty() {
             let page_id = page.get_page_id().unwrap();
-            page.access_page_data(|data| self.disk_manager.write(page_id, data))
+            let guard = page.rlatch();
+            guard.read(|data| self.disk_manager.write(page_id, data))?;
+            page.set_dirty(false);
+            Ok(())
         } else {
             Ok(())
         }
     }
 
+    fn is_draining(&self, frame_id: FrameId) -> bool {
+        self.shrink_to.map_or(false, |size| frame_id >= size)
+    }
+
+    fn pop_free_frame(&mut self) -> Option<FrameId> {
+        let idx = self.free_list.iter().rposition(|frame_id| !self.is_draining(*frame_id))?;
+        self.free_list.remove(idx)
+    }
+
     fn find_fresh_page(&mut self) -> io::Result<Option<(FrameId, Page)>> {
-        if let Some(frame_id) = self.free_list.pop_back() {
+        if let Some(frame_id) = self.pop_free_frame() {
             let mut page = self.pages[frame_id].clone();
             page.pin();
             Ok(Some((frame_id, page)))
-        } else if let Some(frame_id) = self.lru.remove_last() {
+        } else if let Some(frame_id) = self.find_victim() {
             let mut page = self.pages[frame_id].clone();
             self.write_page(&mut page)?;
             page.get_page_id().map(|page_id| self.page_table.remove(&page_id));
+            self.ring_frames.remove(&frame_id);
             page.reset();
             page.pin();
             Ok(Some((frame_id, page)))
@@ -58,18 +85,120 @@ impl UnsafeBufferPoolInstance {
         }
     }
 
-    fn allocate_page(&mut self) -> PageId {
+    /// Takes the least recently used frame that no latch guard holds anymore
+    /// and that is not about to be dropped by a shrinking `resize`. The frames
+    /// skipped keep their place in the replacer.
+    fn find_victim(&mut self) -> Option<FrameId> {
+        let mut skipped = vec![];
+        let mut victim = None;
+        while let Some(frame_id) = self.lru.remove_last() {
+            if self.pages[frame_id].is_in_use() || self.is_draining(frame_id) {
+                skipped.push(frame_id);
+            } else {
+                victim = Some(frame_id);
+                break;
+            }
+        }
+        for frame_id in skipped.into_iter().rev() {
+            self.lru.add_last(frame_id);
+        }
+        victim
+    }
+
+    fn find_fresh_page_with_strategy(&mut self, strategy: &mut AccessStrategy) -> io::Result<Option<(FrameId, Page)>> {
+        match strategy {
+            AccessStrategy::Normal => self.find_fresh_page(),
+            AccessStrategy::BulkRead(ring) => self.find_ring_page(ring, false),
+            AccessStrategy::BulkWrite(ring) => self.find_ring_page(ring, true),
+        }
+    }
+
+    /// Recycles the oldest frame of the ring, unless a `Normal` fetch has hit
+    /// its page since: the frame then left `ring_frames` and is no longer the
+    /// ring's to reuse.
+    fn find_ring_page(&mut self, ring: &mut Ring, reuse_dirty: bool) -> io::Result<Option<(FrameId, Page)>> {
+        if let Some((frame_id, page_id)) = ring.next_victim() {
+            let reusable = self.pages.get(frame_id).filter(|page| {
+                self.ring_frames.contains(&frame_id)
+                    && !self.is_draining(frame_id)
+                    && page.get_page_id() == Some(page_id)
+                    && !page.is_in_use()
+                    && (reuse_dirty || !page.is_dirty())
+            });
+            if let Some(mut page) = reusable.cloned() {
+                self.lru.remove(frame_id);
+                self.write_page(&mut page)?;
+                self.page_table.remove(&page_id);
+                page.reset();
+                page.pin();
+                return Ok(Some((frame_id, page)));
+            }
+        }
+        self.find_fresh_page()
+    }
+
+    fn remember_ring_frame(&mut self, strategy: &mut AccessStrategy, frame_id: FrameId, page_id: PageId) {
+        if let AccessStrategy::BulkRead(ring) | AccessStrategy::BulkWrite(ring) = strategy {
+            ring.push(frame_id, page_id);
+            self.ring_frames.insert(frame_id);
+        }
+    }
+
+    fn record_pin(&mut self, page_id: PageId, location: &'static Location<'static>) {
+        if self.pin_tracking {
+            count_site(&mut self.pin_sites.entry(page_id).or_default().pins, location);
+        }
+    }
+
+    fn record_unpin(&mut self, page_id: PageId, location: &'static Location<'static>, pin_count: u32) {
+        if pin_count == 0 {
+            self.pin_sites.remove(&page_id);
+        } else if let Some(sites) = self.pin_sites.get_mut(&page_id) {
+            count_site(&mut sites.unpins, location);
+        }
+    }
+
+    /// Returns the lowest deallocated page id, or else a new one, and whether
+    /// it was deallocated.
+    fn allocate_page(&mut self) -> (PageId, bool) {
+        if let Some(page_id) = self.free_page_ids.pop_first() {
+            return (page_id, true);
+        }
         let result = self.next_page_id;
         self.next_page_id = (self.inc_fn)(self.next_page_id);
-        result
+        (result, false)
     }
 }
 
+fn count_site(sites: &mut Vec<(&'static Location<'static>, u32)>, location: &'static Location<'static>) {
+    match sites.iter_mut().find(|(site, _count)| *site == location) {
+        Some((_site, count)) => *count += 1,
+        None => sites.push((location, 1)),
+    }
+}
+
+/// A pinned page as reported by `BufferPoolInstance::dump_pinned`.
+///
+/// An unpin does not say which pin it releases, so the leaked pins are not
+/// known exactly: they are `pin_count` of the pins counted in `holders` not
+/// matched by one of the unpins counted in `releases`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PinnedPage {
+    pub page_id: PageId,
+    pub pin_count: u32,
+    /// Call sites of the pins taken since the page was last unpinned
+    /// completely, with the number of pins taken at each, in the order they
+    /// were first hit.
+    pub holders: Vec<(&'static Location<'static>, u32)>,
+    /// Call sites of the unpins since then, counted the same way.
+    pub releases: Vec<(&'static Location<'static>, u32)>,
+}
+
 #[derive(Clone)]
 pub struct BufferPoolInstance(Arc<Mutex<UnsafeBufferPoolInstance>>);
 
 impl BufferPoolInstance {
-    fn new_simple(disk_manager: DiskManager, size: usize) -> Self {
+    pub(crate) fn new_simple(disk_manager: DiskManager, size: usize) -> Self {
         BufferPoolInstance::new(disk_manager, size, 0, Box::new(|page_id| page_id + 1))
     }
 
@@ -82,15 +211,70 @@ impl BufferPoolInstance {
             disk_manager,
             next_page_id,
             inc_fn,
+            free_page_ids: BTreeSet::new(),
             pages,
             free_list: VecDeque::from_iter(0..size),
             page_table: HashMap::with_capacity(size),
+            ring_frames: HashSet::new(),
+            pin_tracking: false,
+            pin_sites: HashMap::new(),
+            shrink_to: None,
         })))
     }
 
-    pub fn flush_page(&self, page_id: PageId) -> io::Result<bool> {
+    /// Enables the debug mode in which every pin taken by `fetch_page` and
+    /// `new_page`, and every `unpin_page` releasing one, records its call site.
+    pub fn set_pin_tracking(&mut self, enabled: bool) {
+        let mut instance = self.0.lock().unwrap();
+        instance.pin_tracking = enabled;
+        if !enabled {
+            instance.pin_sites.clear();
+        }
+    }
+
+    /// Lists the pinned pages ordered by page id. Call sites are only known
+    /// for the pins taken while pin tracking was enabled.
+    pub fn dump_pinned(&self) -> Vec<PinnedPage> {
         let instance = self.0.lock().unwrap();
-        instance.flush_page(page_id)
+
+        let mut pinned: Vec<PinnedPage> = instance
+            .page_table
+            .iter()
+            .map(|(page_id, frame_id)| (*page_id, instance.pages[*frame_id].get_pin_count()))
+            .filter(|(_page_id, pin_count)| *pin_count != 0)
+            .map(|(page_id, pin_count)| {
+                let sites = instance.pin_sites.get(&page_id);
+                PinnedPage {
+                    page_id,
+                    pin_count,
+                    holders: sites.map(|sites| sites.pins.clone()).unwrap_or_default(),
+                    releases: sites.map(|sites| sites.unpins.clone()).unwrap_or_default(),
+                }
+            })
+            .collect();
+        pinned.sort_by_key(|pinned_page| pinned_page.page_id);
+        pinned
+    }
+
+    /// Writes the page if it is resident and dirty. The latch is taken after
+    /// releasing the pool, since a holder of the page may need the pool before
+    /// it lets go of the latch.
+    pub fn flush_page(&self, page_id: PageId) -> io::Result<bool> {
+        let (mut page, disk_manager) = {
+            let instance = self.0.lock().unwrap();
+            match instance.find_page(page_id) {
+                Some((_frame_id, page)) => (page, instance.disk_manager.clone()),
+                None => return Ok(false),
+            }
+        };
+
+        let guard = page.rlatch();
+        // The frame may have been recycled for another page meanwhile.
+        if page.get_page_id() == Some(page_id) && page.is_dirty() {
+            guard.read(|data| disk_manager.write(page_id, data))?;
+            page.set_dirty(false);
+        }
+        Ok(true)
     }
 
     // fn flush_all(&self) -> io::Result<()> {
@@ -102,38 +286,148 @@ impl BufferPoolInstance {
     //     Ok(())
     // }
 
+    #[track_caller]
     pub fn new_page(&mut self) -> io::Result<Option<Page>> {
+        self.new_page_with_strategy(&mut AccessStrategy::Normal)
+    }
+
+    #[track_caller]
+    pub fn new_page_with_strategy(&mut self, strategy: &mut AccessStrategy) -> io::Result<Option<Page>> {
+        let location = Location::caller();
         let mut instance = self.0.lock().unwrap();
 
-        if let Some((frame_id, mut page)) = instance.find_fresh_page()? {
-            let page_id = instance.allocate_page();
+        if let Some((frame_id, mut page)) = instance.find_fresh_page_with_strategy(strategy)? {
+            let (page_id, reused) = instance.allocate_page();
             instance.page_table.insert(page_id, frame_id);
+            instance.remember_ring_frame(strategy, frame_id, page_id);
+            instance.record_pin(page_id, location);
             page.set_page_id(page_id);
+            if reused {
+                // The frame is zeroed, but the disk still has the data of the
+                // deallocated page until the frame is written.
+                page.set_dirty(true);
+            }
             Ok(Some(page))
         } else {
             Ok(None)
         }
     }
 
+    #[track_caller]
     pub fn fetch_page(&mut self, page_id: PageId) -> io::Result<Option<Page>> {
+        self.fetch_page_with_strategy(page_id, &mut AccessStrategy::Normal)
+    }
+
+    /// Fetches the page using the given strategy. A page that is already
+    /// resident is pinned as usual, only misses are served from the
+    /// strategy's ring. Fetching a deallocated page is an error.
+    #[track_caller]
+    pub fn fetch_page_with_strategy(
+        &mut self,
+        page_id: PageId,
+        strategy: &mut AccessStrategy,
+    ) -> io::Result<Option<Page>> {
+        let location = Location::caller();
         let mut instance = self.0.lock().unwrap();
 
-        if let Some((frame_id, mut page)) = instance.find_page(page_id) {
+        if instance.free_page_ids.contains(&page_id) {
+            Err(io::Error::new(io::ErrorKind::InvalidInput, format!("page {} is deallocated", page_id)))
+        } else if let Some((frame_id, mut page)) = instance.find_page(page_id) {
             instance.lru.remove(frame_id);
+            if let AccessStrategy::Normal = strategy {
+                instance.ring_frames.remove(&frame_id);
+            }
+            instance.record_pin(page_id, location);
             page.pin();
             Ok(Some(page))
-        } else if let Some((frame_id, mut page)) = instance.find_fresh_page()? {
+        } else if let Some((frame_id, mut page)) = instance.find_fresh_page_with_strategy(strategy)? {
             instance.lru.remove(frame_id);
             instance.page_table.insert(page_id, frame_id);
+            instance.remember_ring_frame(strategy, frame_id, page_id);
+            instance.record_pin(page_id, location);
             page.set_page_id(page_id);
-            page.access_page_data(|data| instance.disk_manager.read(page_id, data))?;
+            page.load_data(|data| instance.disk_manager.read(page_id, data))?;
             Ok(Some(page))
         } else {
             Ok(None)
         }
     }
 
-    pub fn unpin_page(&mut self, page_id: PageId, is_dirty: bool) -> io::Result<bool> {
+    /// Loads the page into a free frame without pinning it. Returns `false`
+    /// when there is no free frame left: prefetching never evicts pages.
+    pub fn prefetch_page(&mut self, page_id: PageId) -> io::Result<bool> {
+        let mut instance = self.0.lock().unwrap();
+
+        if instance.find_page(page_id).is_some() {
+            Ok(true)
+        } else if let Some(frame_id) = instance.pop_free_frame() {
+            let mut page = instance.pages[frame_id].clone();
+            page.set_page_id(page_id);
+            if let Err(e) = page.load_data(|data| instance.disk_manager.read(page_id, data)) {
+                page.reset();
+                instance.free_list.push_back(frame_id);
+                return Err(e);
+            }
+            instance.page_table.insert(page_id, frame_id);
+            instance.lru.add_last(frame_id);
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Writes the ids of the resident pages to a sidecar file: pinned pages
+    /// first in page id order, since the replacer does not order them,
+    /// followed by the rest in replacer order from the most recently used one.
+    /// Returns the number of saved pages.
+    pub fn save_resident_pages<P: AsRef<Path>>(&self, path: P) -> io::Result<usize> {
+        let instance = self.0.lock().unwrap();
+
+        let mut page_ids: Vec<PageId> = instance
+            .page_table
+            .iter()
+            .filter(|(_page_id, frame_id)| instance.pages[**frame_id].get_pin_count() != 0)
+            .map(|(page_id, _frame_id)| *page_id)
+            .collect();
+        page_ids.sort_unstable();
+        let unpinned = instance.lru.frames().into_iter();
+        page_ids.extend(unpinned.filter_map(|frame_id| instance.pages[frame_id].get_page_id()));
+
+        warm_up::write_page_ids(path, &page_ids)?;
+        Ok(page_ids.len())
+    }
+
+    /// Reloads the pages saved by `save_resident_pages` in the background.
+    /// The hottest pages that fit into the free frames are read in page id
+    /// order, so the data file is scanned sequentially. Warm-up stops as soon
+    /// as the free frames run out. The handle returns the number of loaded
+    /// pages.
+    pub fn warm_up<P: AsRef<Path>>(&self, path: P) -> thread::JoinHandle<io::Result<usize>> {
+        let mut instance = self.clone();
+        let path: PathBuf = path.as_ref().to_path_buf();
+
+        thread::spawn(move || {
+            let free_frames = instance.0.lock().unwrap().free_list.len();
+            let mut page_ids = warm_up::read_page_ids(path)?;
+            page_ids.truncate(free_frames);
+            page_ids.sort_unstable();
+
+            let mut loaded = 0;
+            for page_id in page_ids {
+                if !instance.prefetch_page(page_id)? {
+                    break;
+                }
+                loaded += 1;
+            }
+            Ok(loaded)
+        })
+    }
+
+    /// Releases a pin taken by `fetch_page` or `new_page`. Unpinning a page
+    /// that is not pinned, or not even resident, is an error.
+    #[track_caller]
+    pub fn unpin_page(&mut self, page_id: PageId, is_dirty: bool) -> io::Result<()> {
+        let location = Location::caller();
         let mut instance = self.0.lock().unwrap();
 
         instance
@@ -141,13 +435,83 @@ impl BufferPoolInstance {
             .filter(|(_frame_id, page)| page.get_pin_count() != 0)
             .map(|(frame_id, mut page)| {
                 page.unpin();
-                page.set_dirty(is_dirty);
+                instance.record_unpin(page_id, location, page.get_pin_count());
+                if is_dirty {
+                    page.set_dirty(true);
+                }
                 if page.get_pin_count() == 0 {
-                    instance.lru.add(frame_id);
+                    if instance.ring_frames.contains(&frame_id) {
+                        instance.lru.add_last(frame_id);
+                    } else {
+                        instance.lru.add(frame_id);
+                    }
+                    // A latch guard that outlives the pin keeps the page dirty
+                    // until it is evicted, which waits for the guard.
+                    if !page.is_in_use() {
+                        instance.write_page(&mut page)?;
+                    }
                 }
-                instance.write_page(&mut page).map(|_x| true)
+                Ok(())
+            })
+            .unwrap_or_else(|| {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("page {} is unpinned more times than it was pinned", page_id),
+                ))
             })
-            .unwrap_or(Ok(false))
+    }
+
+    /// Changes the number of frames in the pool, waiting for the pages pinned
+    /// or latched in the highest-numbered frames to be released when
+    /// shrinking. The frames to drop are not handed out to other pages while
+    /// it waits. See `try_resize`.
+    pub fn resize(&mut self, new_size: usize) -> io::Result<()> {
+        {
+            let mut instance = self.0.lock().unwrap();
+            if new_size < instance.pages.len() {
+                instance.shrink_to = Some(new_size);
+            }
+        }
+        loop {
+            match self.try_resize(new_size) {
+                Ok(false) => thread::yield_now(),
+                result => {
+                    self.0.lock().unwrap().shrink_to = None;
+                    return result.map(|_| ());
+                }
+            }
+        }
+    }
+
+    /// Changes the number of frames in the pool. Shrinking drops the
+    /// highest-numbered frames, flushing their pages first if they are
+    /// dirty, and returns `false` without changing anything while any of those
+    /// frames is pinned or latched.
+    pub fn try_resize(&mut self, new_size: usize) -> io::Result<bool> {
+        let mut instance = self.0.lock().unwrap();
+        let size = instance.pages.len();
+
+        if new_size >= size {
+            instance.pages.resize_with(new_size, Page::new);
+            instance.free_list.extend(size..new_size);
+            Ok(true)
+        } else if instance.pages[new_size..].iter().any(Page::is_in_use) {
+            Ok(false)
+        } else {
+            for frame_id in new_size..size {
+                let mut page = instance.pages[frame_id].clone();
+                instance.write_page(&mut page)?;
+            }
+            for frame_id in new_size..size {
+                let page = instance.pages[frame_id].clone();
+                instance.lru.remove(frame_id);
+                instance.ring_frames.remove(&frame_id);
+                page.get_page_id().map(|page_id| instance.page_table.remove(&page_id));
+            }
+            instance.free_list.retain(|frame_id| *frame_id < new_size);
+            instance.pages.truncate(new_size);
+            Ok(true)
+        }
     }
 
     pub fn delete_page(&mut self, page_id: PageId) -> bool {
@@ -155,26 +519,69 @@ impl BufferPoolInstance {
 
         instance
             .find_page(page_id)
-            .filter(|(_frame_id, page)| page.get_pin_count() == 0)
+            .filter(|(_frame_id, page)| !page.is_in_use())
             .map(|(frame_id, mut page)| {
                 page.reset();
                 instance.page_table.remove(&page_id);
+                instance.ring_frames.remove(&frame_id);
                 instance.free_list.push_back(frame_id);
                 true
             })
             .unwrap_or(false)
     }
+
+    /// Drops the page from the pool and hands its id back to the allocator,
+    /// which reuses it for a later `new_page`. Returns `false` without
+    /// changing anything if the page is pinned or already deallocated. The
+    /// deallocated ids are only kept in memory.
+    pub fn deallocate_page(&mut self, page_id: PageId) -> bool {
+        let mut instance = self.0.lock().unwrap();
+
+        if instance.free_page_ids.contains(&page_id) {
+            return false;
+        }
+        match instance.find_page(page_id) {
+            Some((_frame_id, page)) if page.is_in_use() => return false,
+            Some((frame_id, mut page)) => {
+                page.reset();
+                instance.lru.remove(frame_id);
+                instance.page_table.remove(&page_id);
+                instance.ring_frames.remove(&frame_id);
+                instance.free_list.push_back(frame_id);
+            }
+            None => {}
+        }
+        instance.free_page_ids.insert(page_id);
+        true
+    }
+
+    /// Like `deallocate_page`, but waits for the pins and latches that others
+    /// hold on the page instead of returning `false`, e.g. the pin a thread
+    /// keeps for a moment after it released the latch. Returns `false` only if
+    /// the page is already deallocated.
+    pub fn deallocate_page_when_released(&mut self, page_id: PageId) -> bool {
+        loop {
+            if self.deallocate_page(page_id) {
+                return true;
+            } else if self.0.lock().unwrap().free_page_ids.contains(&page_id) {
+                return false;
+            }
+            thread::yield_now();
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use std::io;
 
+    use crate::buffer::access_strategy::AccessStrategy;
     use crate::buffer::buffer_pool_instance::BufferPoolInstance;
     use crate::buffer::constants::PAGE_SIZE;
     use crate::buffer::disk_manager::DiskManager;
     use crate::buffer::test_utils::TempFile;
-    use crate::types::{check_random, RngCore, thread, thread_rng};
+    use crate::buffer::warm_up;
+    use crate::types::{check_random, thread, thread_rng, RngCore};
 
     #[test]
     fn should_run_scenario() -> io::Result<()> {
@@ -188,7 +595,7 @@ mod test {
         assert_eq!(page0.as_ref().and_then(|page| page.get_page_id()), Some(0));
 
         let mut copy0 = [0; PAGE_SIZE];
-        page0.as_mut().unwrap().access_page_data(|data| {
+        page0.as_mut().unwrap().write_data(|data| {
             thread_rng().fill_bytes(data);
             copy0.clone_from_slice(data);
         });
@@ -205,7 +612,7 @@ mod test {
         }
 
         for page_id in 0..5 {
-            assert!(instance.unpin_page(page_id, true)?);
+            instance.unpin_page(page_id, true)?;
         }
 
         for i in 0..5 {
@@ -213,16 +620,16 @@ mod test {
             let page_id = page.as_ref().unwrap().get_page_id().unwrap();
             assert!(page.is_some());
             assert_eq!(page_id, size + i);
-            assert!(instance.unpin_page(page_id, false)?);
+            instance.unpin_page(page_id, false)?;
         }
 
-        page0.as_mut().unwrap().access_page_data(|data| {
+        page0.as_mut().unwrap().read_data(|data| {
             let empty_page = [0; PAGE_SIZE];
             assert_eq!(data, empty_page);
         });
 
         page0 = instance.fetch_page(0)?;
-        page0.as_mut().unwrap().access_page_data(|data| {
+        page0.as_mut().unwrap().read_data(|data| {
             assert_eq!(data, copy0);
         });
 
@@ -238,7 +645,7 @@ mod test {
 
         let mut page = instance.fetch_page(0)?;
         assert!(page.is_some());
-        page.as_mut().unwrap().access_page_data(|data| {
+        page.as_mut().unwrap().read_data(|data| {
             let empty_page = [0; PAGE_SIZE];
             assert_eq!(data, empty_page);
         });
@@ -257,28 +664,79 @@ mod test {
         assert!(page0.is_some());
 
         let mut copy0 = [0; PAGE_SIZE];
-        page0.as_mut().unwrap().access_page_data(|data| {
+        page0.as_mut().unwrap().write_data(|data| {
             thread_rng().fill_bytes(data);
             copy0.clone_from_slice(data);
         });
 
-        assert!(instance.unpin_page(0, true)?);
+        instance.unpin_page(0, true)?;
         assert!(instance.delete_page(0));
 
         let another_page = instance.new_page()?;
         assert!(another_page.is_some());
         assert_eq!(another_page.unwrap().get_page_id(), Some(1));
-        assert!(instance.unpin_page(1, true)?);
+        instance.unpin_page(1, true)?;
         assert!(instance.delete_page(1));
 
         page0 = instance.fetch_page(0)?;
-        page0.as_mut().unwrap().access_page_data(|data| {
+        page0.as_mut().unwrap().read_data(|data| {
             assert_eq!(data, copy0);
         });
 
         Ok(())
     }
 
+    #[test]
+    fn should_reuse_deallocated_page() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let disk_manager = DiskManager::new(file.path())?;
+        let mut instance = BufferPoolInstance::new_simple(disk_manager, 1);
+
+        let mut page0 = instance.new_page()?.unwrap();
+        page0.write_data(|data| thread_rng().fill_bytes(data));
+        assert!(!instance.deallocate_page(0));
+        instance.unpin_page(0, true)?;
+        assert!(instance.deallocate_page(0));
+        assert!(!instance.deallocate_page(0));
+        let freed = instance.fetch_page(0).err().map(|err| err.kind());
+        assert_eq!(freed, Some(io::ErrorKind::InvalidInput));
+
+        let page = instance.new_page()?.unwrap();
+        assert_eq!(page.get_page_id(), Some(0));
+        instance.unpin_page(0, false)?;
+        assert_eq!(instance.new_page()?.unwrap().get_page_id(), Some(1));
+        instance.unpin_page(1, false)?;
+
+        // The reused page is zeroed on disk too, once it is evicted.
+        instance.fetch_page(0)?.unwrap().read_data(|data| assert!(data.iter().all(|byte| *byte == 0)));
+        instance.unpin_page(0, false)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_keep_page_latched_after_unpin() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let disk_manager = DiskManager::new(file.path())?;
+        let mut instance = BufferPoolInstance::new_simple(disk_manager, 1);
+
+        let mut page = instance.new_page()?.unwrap();
+        let mut guard = page.wlatch();
+        guard.write(|data| data[0] = 1);
+        instance.unpin_page(0, true)?;
+        assert!(!instance.delete_page(0));
+        assert!(instance.new_page()?.is_none());
+
+        guard.write(|data| data[1] = 2);
+        drop(guard);
+        assert_eq!(instance.new_page()?.unwrap().get_page_id(), Some(1));
+        instance.unpin_page(1, false)?;
+        instance.fetch_page(0)?.unwrap().read_data(|data| assert_eq!(data[..2], [1, 2]));
+        instance.unpin_page(0, false)?;
+
+        Ok(())
+    }
+
     #[test]
     fn should_not_flush_when_page_does_not_exist() -> io::Result<()> {
         let file = TempFile::new()?;
@@ -316,6 +774,383 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn should_grow_pool() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let disk_manager = DiskManager::new(file.path())?;
+        let mut instance = BufferPoolInstance::new_simple(disk_manager, 1);
+
+        assert!(instance.new_page()?.is_some());
+        assert!(instance.new_page()?.is_none());
+
+        instance.resize(3)?;
+        assert!(instance.new_page()?.is_some());
+        assert!(instance.new_page()?.is_some());
+        assert!(instance.new_page()?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_shrink_pool_and_flush_dirty_pages() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let disk_manager = DiskManager::new(file.path())?;
+        let mut instance = BufferPoolInstance::new_simple(disk_manager, 3);
+
+        let mut copies = vec![];
+        for page_id in 0..3 {
+            let mut page = instance.new_page()?.unwrap();
+            let mut copy = [0; PAGE_SIZE];
+            page.write_data(|data| {
+                thread_rng().fill_bytes(data);
+                copy.clone_from_slice(data);
+            });
+            page.set_dirty(true);
+            copies.push(copy);
+            instance.unpin_page(page_id, true)?;
+        }
+
+        instance.resize(1)?;
+        assert!(instance.new_page()?.is_some());
+        assert!(instance.new_page()?.is_none());
+        instance.unpin_page(3, false)?;
+
+        for (page_id, copy) in copies.iter().enumerate() {
+            let page = instance.fetch_page(page_id)?.unwrap();
+            page.read_data(|data| assert_eq!(data, copy));
+            instance.unpin_page(page_id, false)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_not_shrink_pool_with_pinned_frames() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let disk_manager = DiskManager::new(file.path())?;
+        let mut instance = BufferPoolInstance::new_simple(disk_manager, 2);
+
+        assert!(instance.new_page()?.is_some());
+        assert!(instance.new_page()?.is_some());
+        assert!(!instance.try_resize(1)?);
+
+        instance.unpin_page(0, false)?;
+        instance.unpin_page(1, false)?;
+        assert!(instance.try_resize(1)?);
+        assert!(instance.fetch_page(1)?.is_some());
+        assert!(instance.fetch_page(0)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_shrink_pool_once_pinned_frames_are_unpinned() {
+        check_random(
+            || {
+                let file = TempFile::new().unwrap();
+                let disk_manager = DiskManager::new(file.path()).unwrap();
+                let mut instance = BufferPoolInstance::new_simple(disk_manager, 2);
+                assert!(instance.new_page().unwrap().is_some());
+
+                let mut resizing = instance.clone();
+                let handle = thread::spawn(move || resizing.resize(1));
+                while instance.0.lock().unwrap().shrink_to.is_none() {
+                    thread::yield_now();
+                }
+
+                assert!(instance.new_page().unwrap().is_some());
+                assert!(instance.new_page().unwrap().is_none());
+                instance.unpin_page(1, false).unwrap();
+                instance.unpin_page(0, false).unwrap();
+                handle.join().unwrap().unwrap();
+
+                assert_eq!(instance.0.lock().unwrap().pages.len(), 1);
+                assert!(instance.fetch_page(1).unwrap().is_some());
+                assert!(instance.fetch_page(0).unwrap().is_none());
+            },
+            100,
+        );
+    }
+
+    #[test]
+    fn should_keep_hot_pages_during_bulk_read() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let disk_manager = DiskManager::new(file.path())?;
+        let mut instance = BufferPoolInstance::new_simple(disk_manager, 4);
+
+        for page_id in 0..2 {
+            assert!(instance.fetch_page(page_id)?.is_some());
+            instance.unpin_page(page_id, false)?;
+        }
+
+        let mut strategy = AccessStrategy::bulk_read(1);
+        for page_id in 10..20 {
+            assert!(instance.fetch_page_with_strategy(page_id, &mut strategy)?.is_some());
+            instance.unpin_page(page_id, false)?;
+        }
+
+        let unsafe_instance = instance.0.lock().unwrap();
+        assert!(unsafe_instance.page_table.contains_key(&0));
+        assert!(unsafe_instance.page_table.contains_key(&1));
+        assert!(unsafe_instance.page_table.contains_key(&19));
+        assert_eq!(unsafe_instance.free_list.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_evict_scanned_pages_first() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let disk_manager = DiskManager::new(file.path())?;
+        let mut instance = BufferPoolInstance::new_simple(disk_manager, 2);
+
+        assert!(instance.fetch_page(0)?.is_some());
+        instance.unpin_page(0, false)?;
+        assert!(instance.fetch_page_with_strategy(1, &mut AccessStrategy::bulk_read(1))?.is_some());
+        instance.unpin_page(1, false)?;
+
+        assert!(instance.fetch_page(2)?.is_some());
+
+        let unsafe_instance = instance.0.lock().unwrap();
+        assert!(unsafe_instance.page_table.contains_key(&0));
+        assert!(!unsafe_instance.page_table.contains_key(&1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_not_recycle_ring_page_hit_by_normal_fetch() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let disk_manager = DiskManager::new(file.path())?;
+        let mut instance = BufferPoolInstance::new_simple(disk_manager, 4);
+
+        let mut strategy = AccessStrategy::bulk_read(2);
+        for page_id in 10..12 {
+            assert!(instance.fetch_page_with_strategy(page_id, &mut strategy)?.is_some());
+            instance.unpin_page(page_id, false)?;
+        }
+        assert!(instance.fetch_page(10)?.is_some());
+        instance.unpin_page(10, false)?;
+
+        for page_id in 12..16 {
+            assert!(instance.fetch_page_with_strategy(page_id, &mut strategy)?.is_some());
+            instance.unpin_page(page_id, false)?;
+        }
+
+        let unsafe_instance = instance.0.lock().unwrap();
+        assert!(unsafe_instance.page_table.contains_key(&10));
+        assert!(unsafe_instance.page_table.contains_key(&15));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_recycle_ring_during_bulk_write() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let disk_manager = DiskManager::new(file.path())?;
+        let mut instance = BufferPoolInstance::new_simple(disk_manager, 4);
+
+        assert!(instance.fetch_page(100)?.is_some());
+        instance.unpin_page(100, false)?;
+
+        let mut strategy = AccessStrategy::bulk_write(2);
+        let mut copies = vec![];
+        for _ in 0..6 {
+            let mut page = instance.new_page_with_strategy(&mut strategy)?.unwrap();
+            let mut copy = [0; PAGE_SIZE];
+            page.write_data(|data| {
+                thread_rng().fill_bytes(data);
+                copy.clone_from_slice(data);
+            });
+            copies.push((page.get_page_id().unwrap(), copy));
+            instance.unpin_page(page.get_page_id().unwrap(), true)?;
+        }
+
+        assert!(instance.0.lock().unwrap().page_table.contains_key(&100));
+        for (page_id, copy) in copies {
+            let page = instance.fetch_page(page_id)?.unwrap();
+            page.read_data(|data| assert_eq!(data, copy));
+            instance.unpin_page(page_id, false)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_prefetch_only_into_free_frames() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let disk_manager = DiskManager::new(file.path())?;
+        let mut instance = BufferPoolInstance::new_simple(disk_manager, 2);
+
+        assert!(instance.fetch_page(0)?.is_some());
+        assert!(instance.prefetch_page(1)?);
+        assert!(instance.prefetch_page(1)?);
+        assert!(!instance.prefetch_page(2)?);
+
+        assert!(instance.fetch_page(1)?.is_some());
+        instance.unpin_page(1, false)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_save_and_warm_up_resident_pages() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let sidecar = TempFile::new()?;
+        let size = 4;
+
+        let mut copies = vec![];
+        {
+            let disk_manager = DiskManager::new(file.path())?;
+            let mut instance = BufferPoolInstance::new_simple(disk_manager, size);
+            for page_id in 0..6 {
+                let mut page = instance.new_page()?.unwrap();
+                let mut copy = [0; PAGE_SIZE];
+                page.write_data(|data| {
+                    thread_rng().fill_bytes(data);
+                    copy.clone_from_slice(data);
+                });
+                copies.push(copy);
+                instance.unpin_page(page_id, true)?;
+            }
+            assert!(instance.fetch_page(3)?.is_some());
+
+            assert_eq!(instance.save_resident_pages(sidecar.path())?, size);
+            assert_eq!(warm_up::read_page_ids(sidecar.path())?, vec![3, 5, 4, 2]);
+        }
+
+        let disk_manager = DiskManager::new(file.path())?;
+        let mut instance = BufferPoolInstance::new_simple(disk_manager, 3);
+        assert_eq!(instance.warm_up(sidecar.path()).join().unwrap()?, 3);
+
+        {
+            let unsafe_instance = instance.0.lock().unwrap();
+            assert!(unsafe_instance.free_list.is_empty());
+            assert!([3, 4, 5].iter().all(|page_id| unsafe_instance.page_table.contains_key(page_id)));
+        }
+
+        let page = instance.fetch_page(5)?.unwrap();
+        page.read_data(|data| assert_eq!(data, copies[5]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_save_pinned_pages_in_page_id_order() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let sidecar = TempFile::new()?;
+        let disk_manager = DiskManager::new(file.path())?;
+        let mut instance = BufferPoolInstance::new_simple(disk_manager, 8);
+
+        for page_id in 0..8 {
+            assert!(instance.new_page()?.is_some());
+            if page_id % 3 == 0 {
+                instance.unpin_page(page_id, false)?;
+            }
+        }
+
+        assert_eq!(instance.save_resident_pages(sidecar.path())?, 8);
+        assert_eq!(warm_up::read_page_ids(sidecar.path())?, vec![1, 2, 4, 5, 7, 6, 3, 0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_dump_pinned_pages_with_holders() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let disk_manager = DiskManager::new(file.path())?;
+        let mut instance = BufferPoolInstance::new_simple(disk_manager, 3);
+        instance.set_pin_tracking(true);
+
+        let new_page_line = line!() + 1;
+        assert!(instance.new_page()?.is_some());
+        let fetch_page_line = line!() + 1;
+        assert!(instance.fetch_page(0)?.is_some());
+        assert!(instance.new_page()?.is_some());
+        instance.unpin_page(1, false)?;
+
+        let pinned = instance.dump_pinned();
+        assert_eq!(pinned.len(), 1);
+        assert_eq!(pinned[0].page_id, 0);
+        assert_eq!(pinned[0].pin_count, 2);
+        let lines: Vec<(u32, u32)> =
+            pinned[0].holders.iter().map(|(location, count)| (location.line(), *count)).collect();
+        assert_eq!(lines, vec![(new_page_line, 1), (fetch_page_line, 1)]);
+        assert!(pinned[0].holders.iter().all(|(location, _count)| location.file() == file!()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_report_unpin_sites_with_holders() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let disk_manager = DiskManager::new(file.path())?;
+        let mut instance = BufferPoolInstance::new_simple(disk_manager, 1);
+        instance.set_pin_tracking(true);
+
+        let first_line = line!() + 1;
+        assert!(instance.new_page()?.is_some());
+        let second_line = line!() + 1;
+        assert!(instance.fetch_page(0)?.is_some());
+        let unpin_line = line!() + 1;
+        instance.unpin_page(0, false)?;
+
+        let pinned = instance.dump_pinned();
+        assert_eq!(pinned[0].pin_count, 1);
+        let holders: Vec<u32> = pinned[0].holders.iter().map(|(location, _count)| location.line()).collect();
+        let releases: Vec<u32> = pinned[0].releases.iter().map(|(location, _count)| location.line()).collect();
+        assert_eq!(holders, vec![first_line, second_line]);
+        assert_eq!(releases, vec![unpin_line]);
+
+        instance.unpin_page(0, false)?;
+        assert!(instance.fetch_page(0)?.is_some());
+        assert_eq!(instance.dump_pinned()[0].holders.len(), 1);
+        assert!(instance.dump_pinned()[0].releases.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_count_repeated_pin_sites() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let disk_manager = DiskManager::new(file.path())?;
+        let mut instance = BufferPoolInstance::new_simple(disk_manager, 1);
+        instance.set_pin_tracking(true);
+
+        assert!(instance.new_page()?.is_some());
+        for _ in 0..1000 {
+            assert!(instance.fetch_page(0)?.is_some());
+            instance.unpin_page(0, false)?;
+        }
+
+        let pinned = instance.dump_pinned();
+        assert_eq!(pinned[0].pin_count, 1);
+        let holders: Vec<u32> = pinned[0].holders.iter().map(|(_location, count)| *count).collect();
+        let releases: Vec<u32> = pinned[0].releases.iter().map(|(_location, count)| *count).collect();
+        assert_eq!(holders, vec![1, 1000]);
+        assert_eq!(releases, vec![1000]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_report_excessive_unpin() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let disk_manager = DiskManager::new(file.path())?;
+        let mut instance = BufferPoolInstance::new_simple(disk_manager, 1);
+
+        assert!(instance.new_page()?.is_some());
+        instance.unpin_page(0, false)?;
+        assert_eq!(instance.unpin_page(0, false).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(instance.unpin_page(1, false).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+        instance.set_pin_tracking(true);
+        assert_eq!(instance.unpin_page(0, false).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert!(instance.dump_pinned().is_empty());
+
+        Ok(())
+    }
+
     #[test]
     fn should_work_concurrently() {
         check_random(
@@ -329,7 +1164,7 @@ mod test {
                 let handle1 = thread::spawn(move || {
                     let mut page = instance1.fetch_page(0).unwrap().unwrap();
                     let mut copy = [0; PAGE_SIZE];
-                    page.access_page_data(|data| {
+                    page.write_data(|data| {
                         thread_rng().fill_bytes(data);
                         copy.clone_from_slice(data);
                     });
@@ -340,14 +1175,14 @@ mod test {
                     instance1.delete_page(0);
 
                     page = instance1.fetch_page(0).unwrap().unwrap();
-                    page.access_page_data(|data| assert_eq!(data, copy));
+                    page.read_data(|data| assert_eq!(data, copy));
                 });
 
                 let mut instance2 = instance.clone();
                 let handle2 = thread::spawn(move || {
                     let mut page = instance2.fetch_page(1).unwrap().unwrap();
                     let mut copy = [0; PAGE_SIZE];
-                    page.access_page_data(|data| {
+                    page.write_data(|data| {
                         thread_rng().fill_bytes(data);
                         copy.clone_from_slice(data);
                     });
@@ -358,7 +1193,7 @@ mod test {
                     instance2.delete_page(1);
 
                     page = instance2.fetch_page(1).unwrap().unwrap();
-                    page.access_page_data(|data| assert_eq!(data, copy));
+                    page.read_data(|data| assert_eq!(data, copy));
                 });
 
                 handle1.join().unwrap();
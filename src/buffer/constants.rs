@@ -1 +1 @@
-pub(super) const PAGE_SIZE: usize = 4096;
+pub(crate) const PAGE_SIZE: usize = 4096;
@@ -18,11 +18,11 @@ pub struct DiskManager(Arc<RwLock<UnsafeDiskManager>>);
 
 impl DiskManager {
     pub fn new<P: AsRef<Path>>(path: P) -> io::Result<DiskManager> {
-        let file = File::options().create(true).read(true).write(true).append(true).open(path)?;
+        let file = File::options().create(true).read(true).write(true).open(path)?;
         Ok(DiskManager(Arc::new(RwLock::new(UnsafeDiskManager { file }))))
     }
 
-    pub fn write(&self, page_id: PageId, buf: &mut [u8]) -> io::Result<()> {
+    pub fn write(&self, page_id: PageId, buf: &[u8]) -> io::Result<()> {
         let mut manager = self.0.write().unwrap();
 
         manager.file.write_all_at(buf, DiskManager::offset(page_id))?;
@@ -1,3 +1,4 @@
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 
 use crate::types::{Arc, Mutex};
@@ -39,6 +40,24 @@ impl LRU {
         }
     }
 
+    /// Adds the frame as the next eviction candidate instead of as the most
+    /// recently used one.
+    pub fn add_last(&mut self, frame_id: FrameId) {
+        let mut lru = self.0.lock().unwrap();
+        let current_last = lru.last;
+
+        if let Entry::Vacant(entry) = lru.map.entry(frame_id) {
+            entry.insert(Node { previous: current_last, next: None });
+
+            if let Some(current_last) = lru.last {
+                lru.map.get_mut(&current_last).unwrap().next = Some(frame_id);
+            } else {
+                lru.first = Some(frame_id);
+            }
+            lru.last = Some(frame_id);
+        }
+    }
+
     pub fn remove(&mut self, frame_id: FrameId) {
         let mut lru = self.0.lock().unwrap();
 
@@ -81,6 +100,20 @@ impl LRU {
         })
     }
 
+    /// Returns the frames from the most recently used to the next eviction
+    /// candidate.
+    pub fn frames(&self) -> Vec<FrameId> {
+        let lru = self.0.lock().unwrap();
+
+        let mut frames = Vec::with_capacity(lru.map.len());
+        let mut current = lru.first;
+        while let Some(frame_id) = current {
+            frames.push(frame_id);
+            current = lru.map[&frame_id].next;
+        }
+        frames
+    }
+
     fn size(&self) -> usize {
         self.0.lock().unwrap().map.len()
     }
@@ -147,6 +180,22 @@ mod test {
         assert_eq!(lru.size(), 0)
     }
 
+    #[test]
+    fn should_add_last() {
+        let mut lru = LRU::new();
+        lru.add(1);
+        lru.add_last(2);
+        lru.add(3);
+        lru.add_last(4);
+
+        assert_eq!(lru.frames(), vec![3, 1, 2, 4]);
+        assert_eq!(lru.remove_last(), Some(4));
+        assert_eq!(lru.remove_last(), Some(2));
+        assert_eq!(lru.remove_last(), Some(1));
+        assert_eq!(lru.remove_last(), Some(3));
+        assert_eq!(lru.size(), 0);
+    }
+
     #[test]
     fn should_work_concurrently() {
         check_random(
@@ -1,8 +1,11 @@
-mod buffer_pool;
-mod buffer_pool_instance;
-mod constants;
-mod disk_manager;
+mod access_strategy;
+pub(crate) mod buffer_pool;
+pub(crate) mod buffer_pool_instance;
+pub(crate) mod constants;
+pub(crate) mod disk_manager;
 mod lru;
-mod page;
-mod test_utils;
-mod types;
+pub(crate) mod page;
+pub(crate) mod page_view;
+pub(crate) mod test_utils;
+pub(crate) mod types;
+mod warm_up;
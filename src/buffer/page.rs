@@ -1,88 +1,330 @@
 use crate::buffer::constants::PAGE_SIZE;
 use crate::buffer::types::{PageData, PageId};
-use std::sync::{Arc, RwLock};
+use crate::types::{Arc, Condvar, Mutex, RwLock};
 
-struct UnsafePage {
-    data: PageData,
+struct PageMetadata {
     page_id: Option<PageId>,
     is_dirty: bool,
     pin_count: u32,
+    /// The number of latch guards alive, which may outlive the pin they were
+    /// taken under.
+    guard_count: u32,
+}
+
+/// The holders of the latch on the page data.
+#[derive(Default)]
+struct LatchState {
+    readers: u32,
+    writer: bool,
+}
+
+/// The data latch and the metadata are separate locks, so that the buffer pool
+/// can pin and unpin a page while other threads hold its latch. The latch is
+/// not a lock guard on the data, so that latch guards can own their page: the
+/// data lock is only taken for one access, which the latch makes uncontended.
+struct UnsafePage {
+    data: RwLock<PageData>,
+    metadata: RwLock<PageMetadata>,
+    latch: Mutex<LatchState>,
+    latch_released: Condvar,
 }
 
 #[derive(Clone)]
-pub struct Page(Arc<RwLock<UnsafePage>>);
+pub struct Page(Arc<UnsafePage>);
+
+/// Read access to the data of a latched page, which both latch guards give.
+pub trait PageLatch {
+    fn read<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R;
+}
+
+/// A shared latch on the page data. Holds a clone of the page, so it can be
+/// kept across calls to the buffer pool. Like a pin, a live guard keeps the
+/// buffer pool from evicting, writing or deleting the page.
+pub struct PageReadGuard {
+    page: Page,
+}
+
+/// An exclusive latch on the page data. Writing through the guard marks the
+/// page dirty.
+pub struct PageWriteGuard {
+    page: Page,
+}
+
+impl PageWriteGuard {
+    pub fn write<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        self.page.set_dirty(true);
+        f(self.page.0.data.write().unwrap().as_mut_slice())
+    }
+}
+
+impl PageLatch for PageReadGuard {
+    fn read<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(self.page.0.data.read().unwrap().as_slice())
+    }
+}
+
+impl PageLatch for PageWriteGuard {
+    fn read<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(self.page.0.data.read().unwrap().as_slice())
+    }
+}
+
+impl Drop for PageReadGuard {
+    fn drop(&mut self) {
+        self.page.0.latch.lock().unwrap().readers -= 1;
+        self.page.release_latch();
+    }
+}
+
+impl Drop for PageWriteGuard {
+    fn drop(&mut self) {
+        self.page.0.latch.lock().unwrap().writer = false;
+        self.page.release_latch();
+    }
+}
 
 impl Page {
     pub fn new() -> Self {
-        Page(Arc::new(RwLock::new(UnsafePage { data: [0; PAGE_SIZE], page_id: None, is_dirty: false, pin_count: 0 })))
+        Page(Arc::new(UnsafePage {
+            data: RwLock::new([0; PAGE_SIZE]),
+            metadata: RwLock::new(PageMetadata { page_id: None, is_dirty: false, pin_count: 0, guard_count: 0 }),
+            latch: Mutex::new(LatchState::default()),
+            latch_released: Condvar::new(),
+        }))
     }
 
-    pub fn access_page_data<F, R>(&mut self, f: F) -> R
+    pub fn read_data<F, R>(&self, f: F) -> R
+    where
+        F: (FnOnce(&[u8]) -> R),
+    {
+        self.rlatch().read(f)
+    }
+
+    pub fn write_data<F, R>(&mut self, f: F) -> R
     where
         F: (FnOnce(&mut [u8]) -> R),
     {
-        let mut page = self.0.write().unwrap();
-        f(page.data.as_mut_slice())
+        self.wlatch().write(f)
+    }
+
+    pub fn rlatch(&self) -> PageReadGuard {
+        let mut latch = self.0.latch.lock().unwrap();
+        while latch.writer {
+            latch = self.0.latch_released.wait(latch).unwrap();
+        }
+        latch.readers += 1;
+        self.0.metadata.write().unwrap().guard_count += 1;
+        PageReadGuard { page: self.clone() }
+    }
+
+    /// Like `rlatch`, but returns `None` instead of waiting while the page is
+    /// latched exclusively.
+    pub fn try_rlatch(&self) -> Option<PageReadGuard> {
+        let mut latch = self.0.latch.lock().unwrap();
+        if latch.writer {
+            return None;
+        }
+        latch.readers += 1;
+        self.0.metadata.write().unwrap().guard_count += 1;
+        Some(PageReadGuard { page: self.clone() })
+    }
+
+    pub fn wlatch(&mut self) -> PageWriteGuard {
+        let mut latch = self.0.latch.lock().unwrap();
+        while latch.writer || latch.readers != 0 {
+            latch = self.0.latch_released.wait(latch).unwrap();
+        }
+        latch.writer = true;
+        self.0.metadata.write().unwrap().guard_count += 1;
+        PageWriteGuard { page: self.clone() }
+    }
+
+    fn release_latch(&self) {
+        self.0.metadata.write().unwrap().guard_count -= 1;
+        self.0.latch_released.notify_all();
+    }
+
+    /// Gives the buffer pool write access to the data without marking the page
+    /// dirty, e.g. to load it from disk.
+    pub(super) fn load_data<F, R>(&mut self, f: F) -> R
+    where
+        F: (FnOnce(&mut [u8]) -> R),
+    {
+        f(self.0.data.write().unwrap().as_mut_slice())
     }
 
     pub fn get_page_id(&self) -> Option<PageId> {
-        self.0.read().unwrap().page_id
+        self.0.metadata.read().unwrap().page_id
     }
 
     pub fn set_page_id(&mut self, page_id: PageId) {
-        self.0.write().unwrap().page_id = Some(page_id)
+        self.0.metadata.write().unwrap().page_id = Some(page_id)
     }
 
     pub fn is_dirty(&self) -> bool {
-        self.0.read().unwrap().is_dirty
+        self.0.metadata.read().unwrap().is_dirty
     }
 
     pub fn set_dirty(&mut self, is_dirty: bool) {
-        self.0.write().unwrap().is_dirty = is_dirty
+        self.0.metadata.write().unwrap().is_dirty = is_dirty
     }
 
     pub fn get_pin_count(&self) -> u32 {
-        self.0.read().unwrap().pin_count
+        self.0.metadata.read().unwrap().pin_count
+    }
+
+    /// Whether the page is pinned or a latch guard on it is still alive, so
+    /// the buffer pool must leave the frame alone.
+    pub fn is_in_use(&self) -> bool {
+        let metadata = self.0.metadata.read().unwrap();
+        metadata.pin_count != 0 || metadata.guard_count != 0
     }
 
     pub fn pin(&mut self) {
-        let mut page = self.0.write().unwrap();
-        page.pin_count += 1;
+        let mut metadata = self.0.metadata.write().unwrap();
+        metadata.pin_count += 1;
     }
 
     pub fn unpin(&mut self) {
-        let mut page = self.0.write().unwrap();
-        page.pin_count -= 1;
+        let mut metadata = self.0.metadata.write().unwrap();
+        metadata.pin_count -= 1;
     }
 
     pub fn reset(&mut self) {
-        let mut page = self.0.write().unwrap();
-        page.page_id = None;
-        page.data.fill(0);
-        page.is_dirty = false;
+        let mut data = self.0.data.write().unwrap();
+        let mut metadata = self.0.metadata.write().unwrap();
+        metadata.page_id = None;
+        data.fill(0);
+        metadata.is_dirty = false;
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::Page;
+    use super::{Page, PageLatch};
     use crate::buffer::constants::PAGE_SIZE;
-    use crate::types::{thread_rng, RngCore};
+    use crate::types::{check_random, thread, thread_rng, RngCore};
 
     #[test]
     fn should_read_write_page() {
         let mut page = Page::new();
 
         let mut copy = [0; PAGE_SIZE];
-        page.access_page_data(|data| {
+        page.write_data(|data| {
             thread_rng().fill_bytes(data);
             copy.clone_from_slice(data);
         });
+        assert!(page.is_dirty());
+        page.read_data(|data| assert_eq!(copy, data));
 
         page.reset();
-        page.access_page_data(|data| {
+        page.read_data(|data| {
             assert_ne!(copy, data);
         });
+        assert!(!page.is_dirty());
+    }
+
+    #[test]
+    fn should_share_read_latch() {
+        let page = Page::new();
+
+        let guard1 = page.rlatch();
+        let guard2 = page.try_rlatch().unwrap();
+        assert_eq!(guard1.read(|data| data.len()), PAGE_SIZE);
+        guard1.read(|data1| guard2.read(|data2| assert_eq!(data1, data2)));
+        assert_eq!(page.0.latch.lock().unwrap().readers, 2);
+
+        drop(guard1);
+        drop(guard2);
+        assert_eq!(page.0.latch.lock().unwrap().readers, 0);
+    }
+
+    #[test]
+    fn should_not_wait_for_try_read_latch() {
+        let mut page = Page::new();
+
+        let guard = page.wlatch();
+        assert!(page.try_rlatch().is_none());
+
+        drop(guard);
+        let guard = page.try_rlatch();
+        assert!(guard.is_some());
+        assert!(page.try_rlatch().is_some());
+    }
+
+    #[test]
+    fn should_mark_dirty_only_on_write() {
+        let mut page = Page::new();
+
+        let mut guard = page.wlatch();
+        assert_eq!(guard.read(|data| data[0]), 0);
+        assert!(!page.is_dirty());
+
+        guard.write(|data| data[0] = 1);
+        assert!(page.is_dirty());
+        drop(guard);
+
+        page.read_data(|data| assert_eq!(data[0], 1));
+    }
+
+    #[test]
+    fn should_keep_latch_after_page_is_dropped() {
+        let mut page = Page::new();
+        let mut guard = page.wlatch();
+        drop(page);
+
+        guard.write(|data| data[0] = 7);
+        assert_eq!(guard.read(|data| data[0]), 7);
+    }
+
+    #[test]
+    fn should_wait_for_write_latch_until_readers_are_done() {
+        check_random(
+            || {
+                let page = Page::new();
+                let guard = page.rlatch();
+
+                let mut writer = page.clone();
+                let handle = thread::spawn(move || writer.write_data(|data| data[0] = 1));
+                assert_eq!(guard.read(|data| data[0]), 0);
+                drop(guard);
+                handle.join().unwrap();
+
+                assert_eq!(page.read_data(|data| data[0]), 1);
+                assert!(!page.is_in_use());
+            },
+            100,
+        );
+    }
+
+    #[test]
+    fn should_be_in_use_while_guard_is_alive() {
+        let mut page = Page::new();
+        assert!(!page.is_in_use());
+
+        page.pin();
+        let read_guard = page.rlatch();
+        page.unpin();
+        assert!(page.is_in_use());
+        drop(read_guard);
+        assert!(!page.is_in_use());
+
+        let write_guard = page.wlatch();
+        assert!(page.is_in_use());
+        drop(write_guard);
+        assert!(page.try_rlatch().is_some());
+        assert!(!page.is_in_use());
     }
 
     #[test]
@@ -0,0 +1,253 @@
+use std::io;
+use std::ops::Range;
+
+/// The kind of data stored in a page, recorded in its header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum PageType {
+    /// A zeroed page that has not been formatted yet.
+    Invalid = 0,
+    Slotted = 1,
+    Overflow = 2,
+    BPlusTreeHeader = 3,
+    BPlusTreeInternal = 4,
+    BPlusTreeLeaf = 5,
+    HashTableHeader = 6,
+    HashTableDirectory = 7,
+    HashTableBucket = 8,
+}
+
+impl TryFrom<u16> for PageType {
+    type Error = io::Error;
+
+    fn try_from(value: u16) -> io::Result<Self> {
+        match value {
+            0 => Ok(PageType::Invalid),
+            1 => Ok(PageType::Slotted),
+            2 => Ok(PageType::Overflow),
+            3 => Ok(PageType::BPlusTreeHeader),
+            4 => Ok(PageType::BPlusTreeInternal),
+            5 => Ok(PageType::BPlusTreeLeaf),
+            6 => Ok(PageType::HashTableHeader),
+            7 => Ok(PageType::HashTableDirectory),
+            8 => Ok(PageType::HashTableBucket),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown page type {}", value))),
+        }
+    }
+}
+
+/// The fields shared by every page format. They occupy the first
+/// `PageHeader::SIZE` bytes of a page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageHeader {
+    pub page_type: PageType,
+    /// The offset where the free space of the page ends.
+    pub free_space_pointer: u16,
+    pub checksum: u32,
+    pub lsn: u64,
+}
+
+impl PageHeader {
+    pub const SIZE: usize = 16;
+
+    const PAGE_TYPE_OFFSET: usize = 0;
+    const FREE_SPACE_POINTER_OFFSET: usize = 2;
+    const CHECKSUM_OFFSET: usize = 4;
+    const LSN_OFFSET: usize = 8;
+
+    pub fn new(page_type: PageType, free_space_pointer: u16) -> Self {
+        PageHeader { page_type, free_space_pointer, checksum: 0, lsn: 0 }
+    }
+}
+
+/// Typed access to the bytes of a page. All integers are little-endian and
+/// every access is bounds-checked, returning `InvalidInput` instead of
+/// panicking when it does not fit into the page.
+pub struct PageView<B>(B);
+
+impl<B: AsRef<[u8]>> PageView<B> {
+    pub fn new(data: B) -> Self {
+        PageView(data)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.as_ref().len()
+    }
+
+    pub fn read_u8_at(&self, offset: usize) -> io::Result<u8> {
+        Ok(self.read_array_at::<1>(offset)?[0])
+    }
+
+    pub fn read_u16_at(&self, offset: usize) -> io::Result<u16> {
+        self.read_array_at(offset).map(u16::from_le_bytes)
+    }
+
+    pub fn read_u32_at(&self, offset: usize) -> io::Result<u32> {
+        self.read_array_at(offset).map(u32::from_le_bytes)
+    }
+
+    pub fn read_u64_at(&self, offset: usize) -> io::Result<u64> {
+        self.read_array_at(offset).map(u64::from_le_bytes)
+    }
+
+    pub fn read_bytes_at(&self, offset: usize, len: usize) -> io::Result<&[u8]> {
+        let range = self.range(offset, len)?;
+        Ok(&self.0.as_ref()[range])
+    }
+
+    pub fn copy_to_slice(&self, offset: usize, dst: &mut [u8]) -> io::Result<()> {
+        dst.copy_from_slice(self.read_bytes_at(offset, dst.len())?);
+        Ok(())
+    }
+
+    pub fn read_header(&self) -> io::Result<PageHeader> {
+        Ok(PageHeader {
+            page_type: PageType::try_from(self.read_u16_at(PageHeader::PAGE_TYPE_OFFSET)?)?,
+            free_space_pointer: self.read_u16_at(PageHeader::FREE_SPACE_POINTER_OFFSET)?,
+            checksum: self.read_u32_at(PageHeader::CHECKSUM_OFFSET)?,
+            lsn: self.read_u64_at(PageHeader::LSN_OFFSET)?,
+        })
+    }
+
+    fn read_array_at<const N: usize>(&self, offset: usize) -> io::Result<[u8; N]> {
+        let mut array = [0; N];
+        self.copy_to_slice(offset, &mut array)?;
+        Ok(array)
+    }
+
+    fn range(&self, offset: usize, len: usize) -> io::Result<Range<usize>> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.len() => Ok(offset..end),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} bytes at offset {} are out of the page bounds", len, offset),
+            )),
+        }
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> PageView<B> {
+    pub fn write_u8_at(&mut self, offset: usize, value: u8) -> io::Result<()> {
+        self.write_bytes_at(offset, &[value])
+    }
+
+    pub fn write_u16_at(&mut self, offset: usize, value: u16) -> io::Result<()> {
+        self.write_bytes_at(offset, &value.to_le_bytes())
+    }
+
+    pub fn write_u32_at(&mut self, offset: usize, value: u32) -> io::Result<()> {
+        self.write_bytes_at(offset, &value.to_le_bytes())
+    }
+
+    pub fn write_u64_at(&mut self, offset: usize, value: u64) -> io::Result<()> {
+        self.write_bytes_at(offset, &value.to_le_bytes())
+    }
+
+    pub fn write_bytes_at(&mut self, offset: usize, src: &[u8]) -> io::Result<()> {
+        let range = self.range(offset, src.len())?;
+        self.0.as_mut()[range].copy_from_slice(src);
+        Ok(())
+    }
+
+    /// Copies `len` bytes from `src` to `dst` inside the page. The ranges may
+    /// overlap.
+    pub fn copy_within(&mut self, src: usize, dst: usize, len: usize) -> io::Result<()> {
+        let src = self.range(src, len)?;
+        self.range(dst, len)?;
+        self.0.as_mut().copy_within(src, dst);
+        Ok(())
+    }
+
+    pub fn fill(&mut self, offset: usize, len: usize, value: u8) -> io::Result<()> {
+        let range = self.range(offset, len)?;
+        self.0.as_mut()[range].fill(value);
+        Ok(())
+    }
+
+    pub fn write_header(&mut self, header: &PageHeader) -> io::Result<()> {
+        self.write_u16_at(PageHeader::PAGE_TYPE_OFFSET, header.page_type as u16)?;
+        self.write_u16_at(PageHeader::FREE_SPACE_POINTER_OFFSET, header.free_space_pointer)?;
+        self.write_u32_at(PageHeader::CHECKSUM_OFFSET, header.checksum)?;
+        self.write_u64_at(PageHeader::LSN_OFFSET, header.lsn)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use crate::buffer::constants::PAGE_SIZE;
+    use crate::buffer::page::Page;
+    use crate::buffer::types::PageData;
+
+    use super::{PageHeader, PageType, PageView};
+
+    #[test]
+    fn should_read_write_integers() -> io::Result<()> {
+        let mut data: PageData = [0; PAGE_SIZE];
+        let mut view = PageView::new(data.as_mut_slice());
+
+        view.write_u8_at(0, 0xAB)?;
+        view.write_u16_at(1, 0x1234)?;
+        view.write_u32_at(3, 0xDEAD_BEEF)?;
+        view.write_u64_at(PAGE_SIZE - 8, u64::MAX - 1)?;
+
+        assert_eq!(view.read_u8_at(0)?, 0xAB);
+        assert_eq!(view.read_u16_at(1)?, 0x1234);
+        assert_eq!(view.read_u32_at(3)?, 0xDEAD_BEEF);
+        assert_eq!(view.read_u64_at(PAGE_SIZE - 8)?, u64::MAX - 1);
+        assert_eq!(&data[1..3], &[0x34, 0x12]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_reject_out_of_bounds_access() {
+        let mut data: PageData = [0; PAGE_SIZE];
+        let mut view = PageView::new(data.as_mut_slice());
+
+        assert_eq!(view.read_u64_at(PAGE_SIZE - 7).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(view.write_u16_at(PAGE_SIZE, 1).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(view.read_bytes_at(usize::MAX, 2).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(view.copy_within(0, PAGE_SIZE - 1, 2).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn should_copy_slices() -> io::Result<()> {
+        let mut data: PageData = [0; PAGE_SIZE];
+        let mut view = PageView::new(data.as_mut_slice());
+
+        view.write_bytes_at(10, &[1, 2, 3, 4])?;
+        view.copy_within(10, 12, 4)?;
+        assert_eq!(view.read_bytes_at(10, 6)?, &[1, 2, 1, 2, 3, 4]);
+
+        let mut copy = [0; 3];
+        view.copy_to_slice(13, &mut copy)?;
+        assert_eq!(copy, [2, 3, 4]);
+
+        view.fill(10, 6, 0)?;
+        assert_eq!(view.read_bytes_at(10, 6)?, &[0; 6]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_read_write_header() -> io::Result<()> {
+        let mut page = Page::new();
+        let header = PageHeader { page_type: PageType::Invalid, free_space_pointer: 100, checksum: 7, lsn: 42 };
+
+        page.write_data(|data| PageView::new(data).write_header(&header))?;
+        assert_eq!(page.read_data(|data| PageView::new(data).read_header())?, header);
+        assert_eq!(PageHeader::new(PageType::Invalid, 0).lsn, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_reject_unknown_page_type() {
+        let mut data: PageData = [0; PAGE_SIZE];
+        data[0] = 0xFF;
+
+        assert_eq!(PageView::new(data.as_slice()).read_header().unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
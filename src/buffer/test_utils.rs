@@ -11,7 +11,7 @@ use crate::buffer::types::PageData;
 use crate::types::{thread_rng, RngCore};
 
 #[cfg(test)]
-pub(super) struct TempFile {
+pub(crate) struct TempFile {
     path: PathBuf,
 }
 
@@ -44,7 +44,7 @@ impl TempFile {
 }
 
 #[cfg(test)]
-pub(super) fn random_page() -> PageData {
+pub(crate) fn random_page() -> PageData {
     let mut page: PageData = [0; PAGE_SIZE];
     thread_rng().fill_bytes(page.as_mut_slice());
     page
@@ -1,5 +1,5 @@
 use super::constants::PAGE_SIZE;
 
-pub(super) type FrameId = usize;
-pub(super) type PageId = usize;
-pub(super) type PageData = [u8; PAGE_SIZE];
+pub(crate) type FrameId = usize;
+pub(crate) type PageId = usize;
+pub(crate) type PageData = [u8; PAGE_SIZE];
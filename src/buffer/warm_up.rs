@@ -0,0 +1,103 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::buffer::types::PageId;
+
+const MAGIC: &[u8; 4] = b"WARM";
+
+/// Writes the page ids to the sidecar file. The file is written next to the
+/// target, with `.tmp` appended to its name, and renamed over it, so a crash
+/// never leaves a truncated list behind.
+pub(super) fn write_page_ids<P: AsRef<Path>>(path: P, page_ids: &[PageId]) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+
+    let mut writer = BufWriter::new(File::create(&tmp_path)?);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&(page_ids.len() as u64).to_le_bytes())?;
+    for page_id in page_ids {
+        writer.write_all(&(*page_id as u64).to_le_bytes())?;
+    }
+    writer.into_inner()?.sync_all()?;
+
+    fs::rename(tmp_path, path)
+}
+
+/// Reads the page ids in the order they were written. A missing sidecar file
+/// means there is nothing to warm up.
+pub(super) fn read_page_ids<P: AsRef<Path>>(path: P) -> io::Result<Vec<PageId>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    let mut reader = BufReader::new(file);
+
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a warm-up file"));
+    }
+
+    let count = read_u64(&mut reader)?;
+    (0..count).map(|_| read_u64(&mut reader).map(|page_id| page_id as PageId)).collect()
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, io};
+
+    use crate::buffer::test_utils::TempFile;
+
+    use super::{read_page_ids, write_page_ids};
+
+    #[test]
+    fn should_write_read_page_ids() -> io::Result<()> {
+        let file = TempFile::new()?;
+
+        write_page_ids(file.path(), &[5, 1, 3])?;
+        assert_eq!(read_page_ids(file.path())?, vec![5, 1, 3]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_keep_file_with_other_extension() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let neighbour = file.path().with_extension("tmp");
+        fs::write(&neighbour, b"neighbour")?;
+
+        write_page_ids(file.path(), &[2])?;
+        let kept = fs::read(&neighbour);
+        fs::remove_file(&neighbour)?;
+        assert_eq!(kept?, b"neighbour");
+        assert_eq!(read_page_ids(file.path())?, vec![2]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_read_nothing_without_file() -> io::Result<()> {
+        let path = TempFile::new()?.path().to_path_buf();
+
+        assert!(read_page_ids(path)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn should_reject_foreign_file() -> io::Result<()> {
+        let file = TempFile::new()?;
+        std::fs::write(file.path(), b"not a warm-up file")?;
+
+        assert_eq!(read_page_ids(file.path()).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        Ok(())
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+
+use crate::hash_table::{hash_key, mask, HashTableError, DEFAULT_MAX_DEPTH};
+use crate::types::{Arc, RwLock};
+
+#[derive(Debug)]
+struct Bucket<K, V> {
+    depth: u32,
+    tuples: Vec<(K, V)>,
+}
+
+/// A directory slot. It is repointed by a split of the bucket it points to,
+/// which holds the write latch of that bucket meanwhile.
+type Slot<K, V> = RwLock<Arc<RwLock<Bucket<K, V>>>>;
+
+#[derive(Debug)]
+struct Directory<K, V> {
+    depth: u32,
+    slots: Vec<Slot<K, V>>,
+}
+
+impl<K, V> Directory<K, V> {
+    /// Doubles the directory. The new upper half points to the same buckets as
+    /// the lower half.
+    fn expand(&mut self) {
+        let upper: Vec<_> = self.slots.iter().map(|slot| RwLock::new(slot.read().unwrap().clone())).collect();
+        self.slots.extend(upper);
+        self.depth += 1;
+    }
+
+    fn bucket(&self, idx: u32) -> Arc<RwLock<Bucket<K, V>>> {
+        self.slots[idx as usize].read().unwrap().clone()
+    }
+
+    /// Whether the slot still points to the bucket. Once the bucket is
+    /// latched, no split can repoint the slot anymore.
+    fn points_to(&self, idx: u32, bucket: &Arc<RwLock<Bucket<K, V>>>) -> bool {
+        Arc::ptr_eq(&self.slots[idx as usize].read().unwrap(), bucket)
+    }
+}
+
+struct Inner<K, V, S> {
+    directory: RwLock<Directory<K, V>>,
+    bucket_size_limit: usize,
+    max_depth: u32,
+    hash_builder: S,
+}
+
+/// An extendible hash table with the multimap semantics of
+/// `hash_table::HashTable` that can be shared between threads. Clones refer to
+/// the same table.
+///
+/// The directory, every directory slot and every bucket have their own latch.
+/// Lookups, inserts and splits share the directory latch and latch the
+/// buckets they touch, so they run in parallel on different buckets. A split
+/// repoints the slots of its new bucket one at a time while it holds the write
+/// latch of the bucket being split, so the others latch a bucket and then check
+/// that their slot still points to it. Only doubling the directory takes the
+/// directory latch exclusively.
+///
+/// A full bucket whose tuples all share the hash of the new key grows past the
+/// limit instead of splitting, since no split could separate them.
+pub struct ConcurrentHashTable<K, V, S = RandomState> {
+    inner: Arc<Inner<K, V, S>>,
+}
+
+impl<K, V, S> Clone for ConcurrentHashTable<K, V, S> {
+    fn clone(&self) -> Self {
+        ConcurrentHashTable { inner: self.inner.clone() }
+    }
+}
+
+impl<K: Hash + Eq, V> ConcurrentHashTable<K, V, RandomState> {
+    pub fn new(bucket_size_limit: usize) -> Self {
+        ConcurrentHashTable::with_hasher(bucket_size_limit, RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> ConcurrentHashTable<K, V, S> {
+    pub fn with_hasher(bucket_size_limit: usize, hash_builder: S) -> Self {
+        ConcurrentHashTable::with_max_depth(bucket_size_limit, DEFAULT_MAX_DEPTH, hash_builder)
+    }
+
+    pub fn with_max_depth(bucket_size_limit: usize, max_depth: u32, hash_builder: S) -> Self {
+        assert!(bucket_size_limit > 0, "a bucket must hold at least one tuple");
+        assert!((1..=32).contains(&max_depth), "invalid maximum depth {}", max_depth);
+        let slots =
+            (0..2).map(|_| RwLock::new(Arc::new(RwLock::new(Bucket { depth: 1, tuples: Vec::new() })))).collect();
+        let directory = RwLock::new(Directory { depth: 1, slots });
+        ConcurrentHashTable { inner: Arc::new(Inner { directory, bucket_size_limit, max_depth, hash_builder }) }
+    }
+
+    pub fn get(&self, key: &K) -> Vec<V>
+    where
+        V: Clone,
+    {
+        let hash = self.hash(key);
+        let directory = self.inner.directory.read().unwrap();
+        let idx = mask(hash, directory.depth);
+        loop {
+            let bucket = directory.bucket(idx);
+            let latched = bucket.read().unwrap();
+            if directory.points_to(idx, &bucket) {
+                return latched.tuples.iter().filter(|(k, _)| k == key).map(|(_, value)| value.clone()).collect();
+            }
+        }
+    }
+
+    /// Appends the value to the values of the key. Fails if the bucket of the
+    /// key has to split and the directory is already at its maximum depth.
+    pub fn put(&self, key: K, value: V) -> Result<(), HashTableError> {
+        let hash = self.hash(&key);
+        loop {
+            let directory = self.inner.directory.read().unwrap();
+            let idx = mask(hash, directory.depth);
+            let bucket = directory.bucket(idx);
+            let mut latched = bucket.write().unwrap();
+            if !directory.points_to(idx, &bucket) {
+                continue;
+            }
+            if self.has_room(&latched, hash) {
+                latched.tuples.push((key, value));
+                return Ok(());
+            }
+            if latched.depth < directory.depth {
+                self.split_bucket(&directory, idx, &bucket, &mut latched);
+                continue;
+            }
+
+            // The directory may have doubled between the latches, so it is
+            // only doubled if it still has the depth seen here.
+            let depth = directory.depth;
+            drop(latched);
+            drop(directory);
+            let mut directory = self.inner.directory.write().unwrap();
+            if directory.depth == depth {
+                if depth == self.inner.max_depth {
+                    return Err(HashTableError::DirectoryFull { max_depth: self.inner.max_depth });
+                }
+                directory.expand();
+            }
+        }
+    }
+
+    fn has_room(&self, bucket: &Bucket<K, V>, hash: u32) -> bool {
+        bucket.tuples.len() < self.inner.bucket_size_limit || bucket.tuples.iter().all(|(k, _)| self.hash(k) == hash)
+    }
+
+    /// Moves the tuples that differ from the latched bucket in the next hash
+    /// bit to a new bucket, and points every directory slot of that half to it.
+    fn split_bucket(
+        &self,
+        directory: &Directory<K, V>,
+        bucket_idx: u32,
+        old_bucket: &Arc<RwLock<Bucket<K, V>>>,
+        bucket: &mut Bucket<K, V>,
+    ) {
+        bucket.depth += 1;
+        let depth = bucket.depth;
+        let bucket_bits = mask(bucket_idx, depth);
+        let (keep, moved) =
+            std::mem::take(&mut bucket.tuples).into_iter().partition(|(k, _)| mask(self.hash(k), depth) == bucket_bits);
+        bucket.tuples = keep;
+
+        let new_bucket = Arc::new(RwLock::new(Bucket { depth, tuples: moved }));
+        let image_bits = bucket_bits ^ (1 << (depth - 1));
+        for (idx, slot) in directory.slots.iter().enumerate() {
+            if mask(idx as u32, depth) == image_bits {
+                let mut slot = slot.write().unwrap();
+                if Arc::ptr_eq(&slot, old_bucket) {
+                    *slot = new_bucket.clone();
+                }
+            }
+        }
+    }
+
+    fn hash(&self, key: &K) -> u32 {
+        hash_key(&self.inner.hash_builder, key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::hash_table::{BuildIdentityHasher, HashTableError};
+    use crate::types::{check_random, thread};
+
+    use super::ConcurrentHashTable;
+
+    #[test]
+    fn should_put_get() -> Result<(), HashTableError> {
+        let table = ConcurrentHashTable::new(3);
+
+        table.put(10, 11)?;
+        table.put(10, 12)?;
+        assert_eq!(table.get(&10), vec![11, 12]);
+        assert!(table.get(&20).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_put_get_many() -> Result<(), HashTableError> {
+        let table = ConcurrentHashTable::new(16);
+
+        for i in 0..5000 {
+            table.put(i, i)?;
+            table.put(i, 2 * i)?;
+        }
+
+        for i in 0..5000 {
+            assert_eq!(table.get(&i), vec![i, 2 * i]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn should_keep_one_key_in_one_bucket() -> Result<(), HashTableError> {
+        let table = ConcurrentHashTable::with_max_depth(2, 3, BuildIdentityHasher::default());
+
+        for value in 0..50 {
+            table.put(7, value)?;
+        }
+        assert_eq!(table.get(&7), (0..50).collect::<Vec<_>>());
+        assert_eq!(table.inner.directory.read().unwrap().depth, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_fail_when_directory_reaches_max_depth() -> Result<(), HashTableError> {
+        let table = ConcurrentHashTable::with_max_depth(1, 3, BuildIdentityHasher::default());
+
+        table.put(0, 0)?;
+        assert_eq!(table.put(8, 8), Err(HashTableError::DirectoryFull { max_depth: 3 }));
+        assert_eq!(table.get(&0), vec![0]);
+        assert!(table.get(&8).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_work_concurrently() {
+        check_random(
+            || {
+                let table = ConcurrentHashTable::with_hasher(2, BuildIdentityHasher::default());
+                table.put(0, 0).unwrap();
+
+                // Every writer puts its own keys, so the buckets split and the
+                // directory doubles while the others read and write.
+                let writers: Vec<_> = (1..4u32)
+                    .map(|writer| {
+                        let table = table.clone();
+                        thread::spawn(move || {
+                            for key in (0..24).filter(|key| key % 3 == writer - 1) {
+                                table.put(key, key * 10).unwrap();
+                                assert!(table.get(&key).contains(&(key * 10)));
+                                assert_eq!(table.get(&0)[0], 0);
+                            }
+                        })
+                    })
+                    .collect();
+                for writer in writers {
+                    writer.join().unwrap();
+                }
+
+                assert_eq!(table.get(&0), vec![0, 0]);
+                for key in 1..24 {
+                    assert_eq!(table.get(&key), vec![key * 10]);
+                }
+            },
+            100,
+        );
+    }
+
+    #[test]
+    fn should_split_buckets_concurrently_with_reads() {
+        check_random(
+            || {
+                let table = ConcurrentHashTable::with_hasher(1, BuildIdentityHasher::default());
+                table.put(0, 0).unwrap();
+                table.put(4, 40).unwrap();
+                assert_eq!(table.inner.directory.read().unwrap().depth, 3);
+
+                // The odd buckets have a smaller local depth than the
+                // directory, so they split without doubling it.
+                let writer = {
+                    let table = table.clone();
+                    thread::spawn(move || {
+                        for key in [1, 3, 5, 7] {
+                            table.put(key, key * 10).unwrap();
+                            assert_eq!(table.get(&key), vec![key * 10]);
+                        }
+                    })
+                };
+                let readers: Vec<_> = [0, 4]
+                    .into_iter()
+                    .map(|key| {
+                        let table = table.clone();
+                        thread::spawn(move || {
+                            for _ in 0..4 {
+                                assert_eq!(table.get(&key), vec![key * 10]);
+                            }
+                        })
+                    })
+                    .collect();
+
+                // Splits only share the directory latch, so they go on while
+                // it is held here.
+                let directory = table.inner.directory.read().unwrap();
+                writer.join().unwrap();
+                for reader in readers {
+                    reader.join().unwrap();
+                }
+                assert_eq!(directory.depth, 3);
+                drop(directory);
+
+                for key in [0, 1, 3, 4, 5, 7] {
+                    assert_eq!(table.get(&key), vec![key * 10]);
+                }
+            },
+            100,
+        );
+    }
+
+    #[test]
+    fn should_put_same_key_concurrently() {
+        check_random(
+            || {
+                let table = ConcurrentHashTable::with_hasher(2, BuildIdentityHasher::default());
+
+                let writers: Vec<_> = (0..3u32)
+                    .map(|writer| {
+                        let table = table.clone();
+                        thread::spawn(move || {
+                            for value in 0..4 {
+                                table.put(5, writer * 10 + value).unwrap();
+                                table.put(writer * 2, value).unwrap();
+                            }
+                        })
+                    })
+                    .collect();
+                for writer in writers {
+                    writer.join().unwrap();
+                }
+
+                let mut values = table.get(&5);
+                values.sort_unstable();
+                assert_eq!(values, vec![0, 1, 2, 3, 10, 11, 12, 13, 20, 21, 22, 23]);
+                assert_eq!(table.get(&2), vec![0, 1, 2, 3]);
+            },
+            100,
+        );
+    }
+}
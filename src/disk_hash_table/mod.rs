@@ -0,0 +1,287 @@
+use std::io;
+
+use crate::buffer::buffer_pool::{no_free_frame, page_not_deallocated};
+use crate::buffer::buffer_pool_instance::BufferPoolInstance;
+use crate::buffer::types::PageId;
+
+use self::page::{mask, BucketPage, DirectoryPage, HeaderPage, BUCKET_CAPACITY, DIRECTORY_MAX_DEPTH, HEADER_MAX_DEPTH};
+
+mod page;
+
+/// An extendible hash table stored in pages of the buffer pool, with the same
+/// multimap semantics as `hash_table::HashTable`: `put` appends a value and
+/// `get` returns all the values of a key.
+///
+/// The header page picks a directory by the most significant bits of the hash,
+/// and the directory picks a bucket by the least significant bits. A full
+/// bucket is split, doubling its directory first if the bucket already uses
+/// all of its bits.
+pub struct DiskHashTable {
+    pool: BufferPoolInstance,
+    header_page_id: PageId,
+}
+
+impl DiskHashTable {
+    /// Creates a table of `2^header_depth` directories, each of at most
+    /// `2^directory_max_depth` buckets of `bucket_max_size` entries.
+    pub fn create(
+        mut pool: BufferPoolInstance,
+        header_depth: u32,
+        directory_max_depth: u32,
+        bucket_max_size: usize,
+    ) -> io::Result<Self> {
+        if header_depth > HEADER_MAX_DEPTH
+            || directory_max_depth > DIRECTORY_MAX_DEPTH
+            || !(1..=BUCKET_CAPACITY).contains(&bucket_max_size)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid hash table limits {}, {} and {}", header_depth, directory_max_depth, bucket_max_size),
+            ));
+        }
+
+        let header =
+            HeaderPage { directory_max_depth, bucket_max_size, directory_page_ids: vec![None; 1 << header_depth] };
+        let header_page_id = new_page(&mut pool, |data| header.write(data))?;
+        Ok(DiskHashTable { pool, header_page_id })
+    }
+
+    pub fn open(mut pool: BufferPoolInstance, header_page_id: PageId) -> io::Result<Self> {
+        read_page(&mut pool, header_page_id, HeaderPage::read)?;
+        Ok(DiskHashTable { pool, header_page_id })
+    }
+
+    pub fn header_page_id(&self) -> PageId {
+        self.header_page_id
+    }
+
+    pub fn get(&mut self, key: u32) -> io::Result<Vec<u32>> {
+        let hash = hash(key);
+        let header = read_page(&mut self.pool, self.header_page_id, HeaderPage::read)?;
+        let directory_page_id = match header.directory_page_ids[header.directory_index(hash)] {
+            Some(page_id) => page_id,
+            None => return Ok(vec![]),
+        };
+
+        let directory = read_page(&mut self.pool, directory_page_id, DirectoryPage::read)?;
+        let bucket_page_id = directory.bucket_page_ids[directory.bucket_index(hash)];
+        let bucket = read_page(&mut self.pool, bucket_page_id, BucketPage::read)?;
+        Ok(bucket.entries.iter().filter(|(k, _)| *k == key).map(|(_, value)| *value).collect())
+    }
+
+    /// Appends the value to the values of the key. Fails if the bucket of the
+    /// key is full and its directory cannot grow any more.
+    pub fn put(&mut self, key: u32, value: u32) -> io::Result<()> {
+        let hash = hash(key);
+        let header = read_page(&mut self.pool, self.header_page_id, HeaderPage::read)?;
+        let directory_page_id = self.find_or_create_directory(&header, hash)?;
+
+        loop {
+            let mut directory = read_page(&mut self.pool, directory_page_id, DirectoryPage::read)?;
+            let bucket_index = directory.bucket_index(hash);
+            let bucket_page_id = directory.bucket_page_ids[bucket_index];
+            let mut bucket = read_page(&mut self.pool, bucket_page_id, BucketPage::read)?;
+
+            if bucket.entries.len() < header.bucket_max_size {
+                bucket.entries.push((key, value));
+                return write_page(&mut self.pool, bucket_page_id, |data| bucket.write(data));
+            }
+
+            if directory.local_depths[bucket_index] == directory.global_depth {
+                if directory.global_depth == header.directory_max_depth {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("the bucket of key {} is full and its directory cannot grow", key),
+                    ));
+                }
+                directory.grow();
+            }
+            self.split_bucket(&mut directory, bucket_index, bucket)?;
+            write_page(&mut self.pool, directory_page_id, |data| directory.write(data))?;
+        }
+    }
+
+    fn find_or_create_directory(&mut self, header: &HeaderPage, hash: u32) -> io::Result<PageId> {
+        let directory_index = header.directory_index(hash);
+        if let Some(page_id) = header.directory_page_ids[directory_index] {
+            return Ok(page_id);
+        }
+
+        let bucket_page_id = new_page(&mut self.pool, |data| BucketPage::default().write(data))?;
+        let directory_page_id = new_page(&mut self.pool, |data| DirectoryPage::new(bucket_page_id).write(data))?;
+        write_page(&mut self.pool, self.header_page_id, |data| {
+            let mut header = HeaderPage::read(data)?;
+            header.directory_page_ids[directory_index] = Some(directory_page_id);
+            header.write(data)
+        })?;
+        Ok(directory_page_id)
+    }
+
+    /// Moves the entries that differ from the bucket in the next bit of their
+    /// hash to a new bucket, and points the directory slots of that half to it.
+    fn split_bucket(
+        &mut self,
+        directory: &mut DirectoryPage,
+        bucket_index: usize,
+        bucket: BucketPage,
+    ) -> io::Result<()> {
+        let bucket_page_id = directory.bucket_page_ids[bucket_index];
+        let local_depth = directory.local_depths[bucket_index] + 1;
+        let image_bit = 1 << (local_depth - 1);
+
+        let (keep, moved): (Vec<_>, Vec<_>) =
+            bucket.entries.into_iter().partition(|(key, _)| hash(*key) & image_bit == bucket_index as u32 & image_bit);
+        let image = BucketPage { entries: moved };
+        let image_page_id = new_page(&mut self.pool, |data| image.write(data))?;
+        write_page(&mut self.pool, bucket_page_id, |data| BucketPage { entries: keep }.write(data))?;
+
+        for slot in 0..directory.bucket_page_ids.len() {
+            if directory.bucket_page_ids[slot] == bucket_page_id {
+                directory.local_depths[slot] = local_depth;
+                if mask(slot as u32, local_depth) != mask(bucket_index as u32, local_depth) {
+                    directory.bucket_page_ids[slot] = image_page_id;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A fixed hash function, the finalizer of MurmurHash3. The directories depend
+/// on the hashes, so they must not change between runs.
+fn hash(key: u32) -> u32 {
+    let mut hash = key;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^ (hash >> 16)
+}
+
+fn new_page<F>(pool: &mut BufferPoolInstance, f: F) -> io::Result<PageId>
+where
+    F: FnOnce(&mut [u8]) -> io::Result<()>,
+{
+    let mut page = pool.new_page()?.ok_or_else(no_free_frame)?;
+    let page_id = page.get_page_id().unwrap();
+    let written = page.write_data(f);
+    pool.unpin_page(page_id, true)?;
+    if written.is_err() && !pool.deallocate_page(page_id) {
+        return Err(page_not_deallocated(page_id));
+    }
+    written.map(|_| page_id)
+}
+
+fn read_page<F, R>(pool: &mut BufferPoolInstance, page_id: PageId, f: F) -> io::Result<R>
+where
+    F: FnOnce(&[u8]) -> io::Result<R>,
+{
+    let page = pool.fetch_page(page_id)?.ok_or_else(no_free_frame)?;
+    let result = page.read_data(f);
+    pool.unpin_page(page_id, false)?;
+    result
+}
+
+fn write_page<F, R>(pool: &mut BufferPoolInstance, page_id: PageId, f: F) -> io::Result<R>
+where
+    F: FnOnce(&mut [u8]) -> io::Result<R>,
+{
+    let mut page = pool.fetch_page(page_id)?.ok_or_else(no_free_frame)?;
+    let result = page.write_data(f);
+    pool.unpin_page(page_id, true)?;
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use crate::buffer::buffer_pool_instance::BufferPoolInstance;
+    use crate::buffer::disk_manager::DiskManager;
+    use crate::buffer::test_utils::TempFile;
+
+    use super::DiskHashTable;
+
+    fn table(file: &TempFile, header_depth: u32, directory_max_depth: u32, bucket_max_size: usize) -> DiskHashTable {
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path()).unwrap(), 4);
+        DiskHashTable::create(pool, header_depth, directory_max_depth, bucket_max_size).unwrap()
+    }
+
+    #[test]
+    fn should_not_find_non_existent_value() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let mut table = table(&file, 2, 4, 3);
+
+        assert!(table.get(10)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn should_put_get_multiple_values() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let mut table = table(&file, 2, 4, 3);
+
+        table.put(10, 11)?;
+        table.put(10, 12)?;
+        table.put(11, 13)?;
+        assert_eq!(table.get(10)?, vec![11, 12]);
+        assert_eq!(table.get(11)?, vec![13]);
+        Ok(())
+    }
+
+    #[test]
+    fn should_put_get_many() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let mut table = table(&file, 2, 8, 31);
+
+        for i in 1..5000 {
+            table.put(i, i)?;
+            table.put(i, 2 * i)?;
+            assert_eq!(table.get(i)?, vec![i, 2 * i]);
+        }
+
+        for i in 1..5000 {
+            assert_eq!(table.get(i)?, vec![i, 2 * i]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn should_fail_when_directory_cannot_grow() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let mut table = table(&file, 0, 2, 3);
+
+        for value in 0..3 {
+            table.put(7, value)?;
+        }
+        assert_eq!(table.put(7, 3).unwrap_err().kind(), io::ErrorKind::Other);
+        assert_eq!(table.get(7)?, vec![0, 1, 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn should_reopen_table() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 4);
+        let mut table = DiskHashTable::create(pool.clone(), 2, 8, 10)?;
+        for i in 0..500 {
+            table.put(i, i + 1)?;
+        }
+
+        let mut reopened = DiskHashTable::open(pool, table.header_page_id())?;
+        for i in 0..500 {
+            assert_eq!(reopened.get(i)?, vec![i + 1]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn should_reject_invalid_limits() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 4);
+
+        assert_eq!(DiskHashTable::create(pool.clone(), 9, 4, 3).err().unwrap().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(DiskHashTable::create(pool, 2, 4, 0).err().unwrap().kind(), io::ErrorKind::InvalidInput);
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+use std::io;
+
+use crate::buffer::constants::PAGE_SIZE;
+use crate::buffer::page_view::{PageHeader, PageType, PageView};
+use crate::buffer::types::PageId;
+
+const NO_PAGE: u64 = u64::MAX;
+
+const HEADER_DEPTH_OFFSET: usize = PageHeader::SIZE;
+const DIRECTORY_MAX_DEPTH_OFFSET: usize = HEADER_DEPTH_OFFSET + 4;
+const BUCKET_MAX_SIZE_OFFSET: usize = DIRECTORY_MAX_DEPTH_OFFSET + 4;
+const DIRECTORY_PAGE_IDS_OFFSET: usize = BUCKET_MAX_SIZE_OFFSET + 4;
+
+const GLOBAL_DEPTH_OFFSET: usize = PageHeader::SIZE;
+const LOCAL_DEPTHS_OFFSET: usize = GLOBAL_DEPTH_OFFSET + 4;
+const BUCKET_PAGE_IDS_OFFSET: usize = LOCAL_DEPTHS_OFFSET + (1 << DIRECTORY_MAX_DEPTH);
+
+const BUCKET_SIZE_OFFSET: usize = PageHeader::SIZE;
+const ENTRIES_OFFSET: usize = BUCKET_SIZE_OFFSET + 4;
+const ENTRY_SIZE: usize = 8;
+
+/// The largest number of directories a header page can address.
+pub const HEADER_MAX_DEPTH: u32 = 8;
+/// The largest global depth of a directory page.
+pub const DIRECTORY_MAX_DEPTH: u32 = 8;
+/// The number of entries that fit into a bucket page.
+pub const BUCKET_CAPACITY: usize = (PAGE_SIZE - ENTRIES_OFFSET) / ENTRY_SIZE;
+
+/// The first page of a hash table. It maps the most significant bits of a hash
+/// to a directory, and keeps the limits the table was created with.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct HeaderPage {
+    pub directory_max_depth: u32,
+    pub bucket_max_size: usize,
+    /// `2^depth` directories, created on the first insert into them.
+    pub directory_page_ids: Vec<Option<PageId>>,
+}
+
+impl HeaderPage {
+    pub fn depth(&self) -> u32 {
+        self.directory_page_ids.len().trailing_zeros()
+    }
+
+    pub fn directory_index(&self, hash: u32) -> usize {
+        hash.checked_shr(32 - self.depth()).unwrap_or(0) as usize
+    }
+
+    pub fn read(data: &[u8]) -> io::Result<Self> {
+        let view = PageView::new(data);
+        check_page_type(&view, PageType::HashTableHeader)?;
+
+        let depth = view.read_u32_at(HEADER_DEPTH_OFFSET)?;
+        let directory_page_ids = (0..1 << depth)
+            .map(|i| read_page_id(&view, DIRECTORY_PAGE_IDS_OFFSET + i * 8))
+            .collect::<io::Result<_>>()?;
+        Ok(HeaderPage {
+            directory_max_depth: view.read_u32_at(DIRECTORY_MAX_DEPTH_OFFSET)?,
+            bucket_max_size: view.read_u32_at(BUCKET_MAX_SIZE_OFFSET)? as usize,
+            directory_page_ids,
+        })
+    }
+
+    pub fn write(&self, data: &mut [u8]) -> io::Result<()> {
+        let mut view = PageView::new(data);
+        let end = DIRECTORY_PAGE_IDS_OFFSET + self.directory_page_ids.len() * 8;
+        view.write_header(&PageHeader::new(PageType::HashTableHeader, end as u16))?;
+        view.write_u32_at(HEADER_DEPTH_OFFSET, self.depth())?;
+        view.write_u32_at(DIRECTORY_MAX_DEPTH_OFFSET, self.directory_max_depth)?;
+        view.write_u32_at(BUCKET_MAX_SIZE_OFFSET, self.bucket_max_size as u32)?;
+        for (i, page_id) in self.directory_page_ids.iter().enumerate() {
+            write_page_id(&mut view, DIRECTORY_PAGE_IDS_OFFSET + i * 8, *page_id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Maps the least significant `global_depth` bits of a hash to a bucket. Every
+/// slot also records the local depth of its bucket, the number of bits that
+/// all the keys of the bucket share.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct DirectoryPage {
+    pub global_depth: u32,
+    pub local_depths: Vec<u32>,
+    pub bucket_page_ids: Vec<PageId>,
+}
+
+impl DirectoryPage {
+    pub fn new(bucket_page_id: PageId) -> Self {
+        DirectoryPage { global_depth: 0, local_depths: vec![0], bucket_page_ids: vec![bucket_page_id] }
+    }
+
+    pub fn bucket_index(&self, hash: u32) -> usize {
+        mask(hash, self.global_depth) as usize
+    }
+
+    /// Doubles the directory. The new upper half points to the same buckets as
+    /// the lower half.
+    pub fn grow(&mut self) {
+        self.local_depths.extend_from_within(..);
+        self.bucket_page_ids.extend_from_within(..);
+        self.global_depth += 1;
+    }
+
+    pub fn read(data: &[u8]) -> io::Result<Self> {
+        let view = PageView::new(data);
+        check_page_type(&view, PageType::HashTableDirectory)?;
+
+        let global_depth = view.read_u32_at(GLOBAL_DEPTH_OFFSET)?;
+        let size = 1 << global_depth;
+        let local_depths = view.read_bytes_at(LOCAL_DEPTHS_OFFSET, size)?.iter().map(|depth| *depth as u32).collect();
+        let bucket_page_ids = (0..size)
+            .map(|i| view.read_u64_at(BUCKET_PAGE_IDS_OFFSET + i * 8).map(|page_id| page_id as PageId))
+            .collect::<io::Result<_>>()?;
+        Ok(DirectoryPage { global_depth, local_depths, bucket_page_ids })
+    }
+
+    pub fn write(&self, data: &mut [u8]) -> io::Result<()> {
+        let mut view = PageView::new(data);
+        let end = BUCKET_PAGE_IDS_OFFSET + self.bucket_page_ids.len() * 8;
+        view.write_header(&PageHeader::new(PageType::HashTableDirectory, end as u16))?;
+        view.write_u32_at(GLOBAL_DEPTH_OFFSET, self.global_depth)?;
+        let local_depths: Vec<u8> = self.local_depths.iter().map(|depth| *depth as u8).collect();
+        view.write_bytes_at(LOCAL_DEPTHS_OFFSET, &local_depths)?;
+        for (i, page_id) in self.bucket_page_ids.iter().enumerate() {
+            view.write_u64_at(BUCKET_PAGE_IDS_OFFSET + i * 8, *page_id as u64)?;
+        }
+        Ok(())
+    }
+}
+
+/// The `(key, value)` pairs of one bucket in insertion order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(super) struct BucketPage {
+    pub entries: Vec<(u32, u32)>,
+}
+
+impl BucketPage {
+    pub fn read(data: &[u8]) -> io::Result<Self> {
+        let view = PageView::new(data);
+        check_page_type(&view, PageType::HashTableBucket)?;
+
+        let size = view.read_u32_at(BUCKET_SIZE_OFFSET)? as usize;
+        let entries = (0..size)
+            .map(|i| {
+                let offset = ENTRIES_OFFSET + i * ENTRY_SIZE;
+                Ok((view.read_u32_at(offset)?, view.read_u32_at(offset + 4)?))
+            })
+            .collect::<io::Result<_>>()?;
+        Ok(BucketPage { entries })
+    }
+
+    pub fn write(&self, data: &mut [u8]) -> io::Result<()> {
+        let mut view = PageView::new(data);
+        let end = ENTRIES_OFFSET + self.entries.len() * ENTRY_SIZE;
+        view.write_header(&PageHeader::new(PageType::HashTableBucket, end as u16))?;
+        view.write_u32_at(BUCKET_SIZE_OFFSET, self.entries.len() as u32)?;
+        for (i, (key, value)) in self.entries.iter().enumerate() {
+            let offset = ENTRIES_OFFSET + i * ENTRY_SIZE;
+            view.write_u32_at(offset, *key)?;
+            view.write_u32_at(offset + 4, *value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Keeps the `depth` least significant bits.
+pub(super) fn mask(hash: u32, depth: u32) -> u32 {
+    hash & u32::checked_shl(1, depth).unwrap_or(0).wrapping_sub(1)
+}
+
+fn check_page_type<B: AsRef<[u8]>>(view: &PageView<B>, page_type: PageType) -> io::Result<()> {
+    let actual = view.read_header()?.page_type;
+    if actual != page_type {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("expected a {:?} page, found {:?}", page_type, actual),
+        ));
+    }
+    Ok(())
+}
+
+fn read_page_id<B: AsRef<[u8]>>(view: &PageView<B>, offset: usize) -> io::Result<Option<PageId>> {
+    let page_id = view.read_u64_at(offset)?;
+    Ok(Some(page_id as PageId).filter(|_| page_id != NO_PAGE))
+}
+
+fn write_page_id<B: AsRef<[u8]> + AsMut<[u8]>>(
+    view: &mut PageView<B>,
+    offset: usize,
+    page_id: Option<PageId>,
+) -> io::Result<()> {
+    view.write_u64_at(offset, page_id.map_or(NO_PAGE, |page_id| page_id as u64))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use crate::buffer::constants::PAGE_SIZE;
+
+    use super::{BucketPage, DirectoryPage, HeaderPage, BUCKET_CAPACITY, DIRECTORY_MAX_DEPTH, HEADER_MAX_DEPTH};
+
+    #[test]
+    fn should_round_trip_header_page() -> io::Result<()> {
+        let mut directory_page_ids = vec![None; 1 << HEADER_MAX_DEPTH];
+        directory_page_ids[3] = Some(17);
+        let header = HeaderPage { directory_max_depth: 5, bucket_max_size: 10, directory_page_ids };
+        let mut data = [0; PAGE_SIZE];
+
+        header.write(&mut data)?;
+        assert_eq!(HeaderPage::read(&data)?, header);
+        assert_eq!(header.depth(), HEADER_MAX_DEPTH);
+        assert_eq!(header.directory_index(u32::MAX), (1 << HEADER_MAX_DEPTH) - 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_round_trip_directory_page() -> io::Result<()> {
+        let mut directory = DirectoryPage::new(1);
+        for _ in 0..DIRECTORY_MAX_DEPTH {
+            directory.grow();
+        }
+        directory.local_depths[7] = 3;
+        directory.bucket_page_ids[255] = 9;
+        let mut data = [0; PAGE_SIZE];
+
+        directory.write(&mut data)?;
+        assert_eq!(DirectoryPage::read(&data)?, directory);
+        assert!(BucketPage::read(&data).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_round_trip_bucket_page() -> io::Result<()> {
+        let bucket = BucketPage { entries: (0..BUCKET_CAPACITY as u32).map(|i| (i, i * 2)).collect() };
+        let mut data = [0; PAGE_SIZE];
+
+        bucket.write(&mut data)?;
+        assert_eq!(BucketPage::read(&data)?, bucket);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_grow_directory() {
+        let mut directory = DirectoryPage::new(1);
+        directory.grow();
+        directory.bucket_page_ids[1] = 2;
+        directory.local_depths = vec![1, 1];
+        directory.grow();
+
+        assert_eq!(directory.global_depth, 2);
+        assert_eq!(directory.bucket_page_ids, vec![1, 2, 1, 2]);
+        assert_eq!(directory.bucket_index(0b110), 0b10);
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::hash_map::RandomState;
+use std::hash::Hash;
+use std::io;
+use std::vec;
+
+use super::{read_spilled, spill, SpillHeap, SPILL_FANOUT};
+use crate::buffer::buffer_pool_instance::BufferPoolInstance;
+use crate::hash_table::{Codec, Entry, HashTable, Unique};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggregateValue {
+    Int(i64),
+    Float(f64),
+}
+
+/// The running state of a group, enough to compute every aggregate function.
+/// The sum is only kept if a function needs it, so that the other functions
+/// don't fail when it overflows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct AggregateState {
+    count: u64,
+    sum: i64,
+    min: i64,
+    max: i64,
+}
+
+impl AggregateState {
+    fn new() -> Self {
+        AggregateState { count: 0, sum: 0, min: i64::MAX, max: i64::MIN }
+    }
+
+    fn update(&mut self, value: i64, needs_sum: bool) -> io::Result<()> {
+        if needs_sum {
+            self.sum = self
+                .sum
+                .checked_add(value)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the sum of a group overflows"))?;
+        }
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        Ok(())
+    }
+
+    fn value(&self, function: AggregateFunction) -> AggregateValue {
+        match function {
+            AggregateFunction::Count => AggregateValue::Int(self.count as i64),
+            AggregateFunction::Sum => AggregateValue::Int(self.sum),
+            AggregateFunction::Min => AggregateValue::Int(self.min),
+            AggregateFunction::Max => AggregateValue::Int(self.max),
+            AggregateFunction::Avg => AggregateValue::Float(self.sum as f64 / self.count as f64),
+        }
+    }
+}
+
+/// Groups `(key, value)` rows by key and computes aggregate functions of the
+/// values of every group, like `GROUP BY key` with `COUNT(value)`,
+/// `SUM(value)` and so on.
+///
+/// The groups are kept in a unique-key `HashTable` of at most `max_groups`
+/// groups. Once it is full, the rows of new groups are spilled to temporary
+/// table heaps in the buffer pool, one per partition of the key hash, while the
+/// groups in memory keep aggregating. Every group is therefore either wholly in
+/// memory or wholly spilled. `finish` emits the groups in memory first and then
+/// aggregates the partitions one at a time the same way, spilling again with
+/// another hash if a partition still has too many groups.
+pub struct HashAggregate<K> {
+    pool: BufferPoolInstance,
+    functions: Vec<AggregateFunction>,
+    /// Whether `Sum` or `Avg` is computed.
+    needs_sum: bool,
+    max_groups: usize,
+    /// The recursion depth, which seeds the partition hash.
+    level: u32,
+    groups: HashTable<K, AggregateState, RandomState, Unique>,
+    partitions: Vec<Option<SpillHeap>>,
+}
+
+impl<K: Hash + Eq + Codec> HashAggregate<K> {
+    /// Creates an aggregation that keeps at most `max_groups` groups in
+    /// memory. The limit is a number of groups, not of bytes: every group
+    /// counts as one whatever the size of its key.
+    pub fn new(pool: BufferPoolInstance, functions: Vec<AggregateFunction>, max_groups: usize) -> Self {
+        assert!(max_groups > 0, "an aggregation must keep at least one group in memory");
+        HashAggregate::with_level(pool, functions, max_groups, 0)
+    }
+
+    fn with_level(pool: BufferPoolInstance, functions: Vec<AggregateFunction>, max_groups: usize, level: u32) -> Self {
+        let needs_sum =
+            functions.iter().any(|function| matches!(function, AggregateFunction::Sum | AggregateFunction::Avg));
+        HashAggregate {
+            pool,
+            functions,
+            needs_sum,
+            max_groups,
+            level,
+            groups: HashTable::new_unique(64),
+            partitions: (0..SPILL_FANOUT).map(|_| None).collect(),
+        }
+    }
+
+    pub fn add(&mut self, key: K, value: i64) -> io::Result<()> {
+        let is_full = self.groups.len() >= self.max_groups;
+        match self.groups.entry(key) {
+            Entry::Occupied(entry) => entry.into_mut().update(value, self.needs_sum),
+            Entry::Vacant(entry) if !is_full => entry
+                .insert(AggregateState::new())
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+                .update(value, self.needs_sum),
+            Entry::Vacant(entry) => {
+                spill(&self.pool, &mut self.partitions, self.level, &entry.into_key(), &value).map(|_| ())
+            }
+        }
+    }
+
+    /// Whether any rows went to disk.
+    pub fn has_spilled(&self) -> bool {
+        self.partitions.iter().any(Option::is_some)
+    }
+
+    /// Returns the groups with the values of the aggregate functions, in the
+    /// order of the functions.
+    pub fn finish(mut self) -> HashAggregateIter<K> {
+        let groups: Vec<_> = self.groups.drain().collect();
+        HashAggregateIter {
+            pool: self.pool,
+            functions: self.functions,
+            max_groups: self.max_groups,
+            groups: groups.into_iter(),
+            partitions: self.partitions.into_iter().flatten().map(|heap| (self.level + 1, heap)).collect(),
+        }
+    }
+}
+
+/// The groups of a `HashAggregate`. Spilled partitions are aggregated when the
+/// groups before them have been read.
+pub struct HashAggregateIter<K> {
+    pool: BufferPoolInstance,
+    functions: Vec<AggregateFunction>,
+    max_groups: usize,
+    groups: vec::IntoIter<(K, AggregateState)>,
+    /// The spilled partitions not aggregated yet, with the level to aggregate
+    /// them at.
+    partitions: Vec<(u32, SpillHeap)>,
+}
+
+impl<K: Hash + Eq + Codec> HashAggregateIter<K> {
+    /// Aggregates the partition into memory and queues the partitions it
+    /// spills in turn.
+    fn aggregate_partition(&mut self, level: u32, heap: SpillHeap) -> io::Result<()> {
+        let mut aggregate =
+            HashAggregate::with_level(self.pool.clone(), self.functions.clone(), self.max_groups, level);
+        read_spilled(heap, |key, value| aggregate.add(key, value))?;
+
+        self.groups = aggregate.groups.drain().collect::<Vec<_>>().into_iter();
+        self.partitions.extend(aggregate.partitions.into_iter().flatten().map(|heap| (level + 1, heap)));
+        Ok(())
+    }
+}
+
+impl<K: Hash + Eq + Codec> Iterator for HashAggregateIter<K> {
+    type Item = io::Result<(K, Vec<AggregateValue>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((key, state)) = self.groups.next() {
+                let values = self.functions.iter().map(|function| state.value(*function)).collect();
+                return Some(Ok((key, values)));
+            }
+
+            let (level, heap) = self.partitions.pop()?;
+            if let Err(e) = self.aggregate_partition(level, heap) {
+                // Frees the partitions, the iterator ends with the error.
+                self.partitions.clear();
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use std::io;
+
+    use crate::buffer::buffer_pool_instance::BufferPoolInstance;
+    use crate::buffer::disk_manager::DiskManager;
+    use crate::buffer::test_utils::TempFile;
+
+    use super::AggregateFunction::{Avg, Count, Max, Min, Sum};
+    use super::AggregateValue::{Float, Int};
+    use super::{AggregateValue, HashAggregate};
+
+    fn expected(rows: &[(u32, i64)]) -> BTreeMap<u32, Vec<AggregateValue>> {
+        let mut groups: BTreeMap<u32, Vec<i64>> = BTreeMap::new();
+        for (key, value) in rows {
+            groups.entry(*key).or_default().push(*value);
+        }
+        groups
+            .into_iter()
+            .map(|(key, values)| {
+                let sum: i64 = values.iter().sum();
+                let aggregates = vec![
+                    Int(values.len() as i64),
+                    Int(sum),
+                    Int(*values.iter().min().unwrap()),
+                    Int(*values.iter().max().unwrap()),
+                    Float(sum as f64 / values.len() as f64),
+                ];
+                (key, aggregates)
+            })
+            .collect()
+    }
+
+    fn aggregate(
+        pool: BufferPoolInstance,
+        rows: &[(u32, i64)],
+        max_groups: usize,
+    ) -> io::Result<(bool, BTreeMap<u32, Vec<AggregateValue>>)> {
+        let mut aggregate = HashAggregate::new(pool, vec![Count, Sum, Min, Max, Avg], max_groups);
+        for (key, value) in rows {
+            aggregate.add(*key, *value)?;
+        }
+        let spilled = aggregate.has_spilled();
+
+        let mut groups = BTreeMap::new();
+        for group in aggregate.finish() {
+            let (key, values) = group?;
+            assert!(groups.insert(key, values).is_none());
+        }
+        Ok((spilled, groups))
+    }
+
+    #[test]
+    fn should_aggregate_in_memory() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 4);
+        let rows = [(1, 10), (2, -5), (1, 20), (3, 7), (1, -3), (2, 5)];
+
+        let (spilled, groups) = aggregate(pool, &rows, 10)?;
+        assert!(!spilled);
+        assert_eq!(groups, expected(&rows));
+        assert_eq!(groups[&1], vec![Int(3), Int(27), Int(-3), Int(20), Float(9.0)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_spill_partitions_and_recurse() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 8);
+        let rows: Vec<(u32, i64)> = (0..3000).map(|i| ((i * 7) % 500, i as i64 - 1000)).collect();
+
+        let (spilled, groups) = aggregate(pool.clone(), &rows, 4)?;
+        assert!(spilled);
+        assert_eq!(groups, expected(&rows));
+        assert!(pool.dump_pinned().is_empty());
+
+        Ok(())
+    }
+
+    /// Counts the free frames of the pool by prefetching pages into them, so
+    /// it fills them up.
+    fn take_free_frames(pool: &BufferPoolInstance) -> io::Result<usize> {
+        let mut pool = pool.clone();
+        let mut free_frames = 0;
+        while pool.prefetch_page(1000 + free_frames)? {
+            free_frames += 1;
+        }
+        Ok(free_frames)
+    }
+
+    #[test]
+    fn should_free_partitions_of_unfinished_aggregation() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 8);
+        let mut aggregate = HashAggregate::new(pool.clone(), vec![Count], 4);
+        for i in 0..1000u32 {
+            aggregate.add(i % 100, 1)?;
+        }
+        assert!(aggregate.has_spilled());
+
+        drop(aggregate);
+        assert_eq!(take_free_frames(&pool)?, 8);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_free_partitions_of_dropped_iterator() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 8);
+        let mut aggregate = HashAggregate::new(pool.clone(), vec![Count], 4);
+        for i in 0..1000u32 {
+            aggregate.add(i % 100, 1)?;
+        }
+
+        let mut groups = aggregate.finish();
+        for _ in 0..10 {
+            groups.next().unwrap()?;
+        }
+        drop(groups);
+        assert_eq!(take_free_frames(&pool)?, 8);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_group_by_string_keys() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 4);
+        let mut aggregate = HashAggregate::new(pool, vec![Count, Max], 1);
+
+        for (i, name) in ["b", "a", "b", "c", "a", "b"].iter().enumerate() {
+            aggregate.add(name.to_string(), i as i64)?;
+        }
+        let mut groups = aggregate.finish().collect::<io::Result<Vec<_>>>()?;
+        groups.sort_by(|a, b| a.0.cmp(&b.0));
+
+        assert_eq!(
+            groups,
+            vec![
+                ("a".to_string(), vec![Int(2), Int(4)]),
+                ("b".to_string(), vec![Int(3), Int(5)]),
+                ("c".to_string(), vec![Int(1), Int(3)]),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_fail_on_sum_overflow() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 4);
+        let mut aggregate = HashAggregate::new(pool, vec![Sum], 10);
+
+        aggregate.add(1u32, i64::MAX)?;
+        assert_eq!(aggregate.add(1, 1).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_not_sum_without_sum_or_avg() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 8);
+        let mut aggregate = HashAggregate::new(pool, vec![Count, Min, Max], 2);
+
+        // Some of the groups spill and are aggregated again from disk.
+        for key in 0..10u32 {
+            aggregate.add(key, i64::MAX)?;
+            aggregate.add(key, i64::MAX)?;
+            aggregate.add(key, i64::MIN)?;
+        }
+        assert!(aggregate.has_spilled());
+
+        let mut groups = aggregate.finish().collect::<io::Result<Vec<_>>>()?;
+        groups.sort_unstable_by_key(|(key, _)| *key);
+        let expected: Vec<_> = (0..10).map(|key| (key, vec![Int(3), Int(i64::MIN), Int(i64::MAX)])).collect();
+        assert_eq!(groups, expected);
+
+        Ok(())
+    }
+}
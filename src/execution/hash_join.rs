@@ -0,0 +1,596 @@
+use std::hash::Hash;
+use std::io;
+use std::mem;
+use std::vec;
+
+use crate::buffer::buffer_pool_instance::BufferPoolInstance;
+use crate::hash_table::{Codec, HashTable};
+
+use super::{spill, SpillHeap, SpilledRows, SPILL_FANOUT};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinType {
+    /// Every pair of rows with equal keys.
+    Inner,
+    /// Every pair of rows with equal keys, and the left rows without a match
+    /// paired with `None`.
+    LeftOuter,
+    /// The left rows with at least one match, once each.
+    Semi,
+}
+
+/// The rows of one join input that have not been read yet.
+type Rows<'a, K, T> = Box<dyn Iterator<Item = io::Result<(K, T)>> + 'a>;
+
+/// Joins `(key, value)` rows of two inputs on equal keys, like
+/// `left JOIN right ON left.key = right.key`.
+///
+/// Both inputs are read in turn until one of them ends within
+/// `max_build_rows` rows. That smaller input is put into a `HashTable`, and
+/// the rest of the other one streams through it as the probe side. Only if
+/// both inputs outgrow the limit are they partitioned by key hash into
+/// temporary table heaps in the buffer pool, and the partitions of both sides
+/// with the same hash are joined pair by pair the same way, partitioning again
+/// with another hash if needed. A pair that fits on neither side and would not
+/// fit either when partitioned again, because a side has a key with too many
+/// rows or did not get smaller, is joined with a block nested loop instead:
+/// `max_build_rows` left rows at a time are built on and probed by all of the
+/// right rows.
+pub struct HashJoin {
+    pool: BufferPoolInstance,
+    join_type: JoinType,
+    max_build_rows: usize,
+}
+
+impl HashJoin {
+    pub fn new(pool: BufferPoolInstance, join_type: JoinType, max_build_rows: usize) -> Self {
+        assert!(max_build_rows > 0, "a join must keep at least one row in memory");
+        HashJoin { pool, join_type, max_build_rows }
+    }
+
+    /// Returns the joined rows as `(key, left value, right value)` in no
+    /// particular order. The right value is `None` for the unmatched rows of a
+    /// left outer join and for every row of a semi join. The inputs are only
+    /// read as the rows are.
+    pub fn join<'a, K, L, R, I, J>(self, left: I, right: J) -> HashJoinIter<'a, K, L, R>
+    where
+        K: Hash + Eq + Clone + Codec + 'a,
+        L: Clone + Codec + 'a,
+        R: Clone + Codec + 'a,
+        I: IntoIterator<Item = (K, L)>,
+        I::IntoIter: 'a,
+        J: IntoIterator<Item = (K, R)>,
+        J::IntoIter: 'a,
+    {
+        let left = Box::new(left.into_iter().map(Ok));
+        let right = Box::new(right.into_iter().map(Ok));
+        HashJoinIter { join: self, state: State::Load { level: 0, left, right }, partitions: vec![] }
+    }
+}
+
+enum State<'a, K, L, R> {
+    /// Reading both inputs to pick the side to build on.
+    Load {
+        level: u32,
+        left: Rows<'a, K, L>,
+        right: Rows<'a, K, R>,
+    },
+    ProbeRight(ProbeRight<'a, K, L, R>),
+    /// The left rows a left outer or semi join emits once the right ones have
+    /// probed them, and the rest of a block nested loop join.
+    FinishLeft(vec::IntoIter<(K, L)>, Option<NestedLoop<'a, K, L>>),
+    ProbeLeft(ProbeLeft<'a, K, L, R>),
+    Done,
+}
+
+/// A table built on the left rows and probed by the right ones, keeping track
+/// of the left rows that found a match.
+struct ProbeRight<'a, K, L, R> {
+    left: Vec<(K, L)>,
+    table: HashTable<K, usize>,
+    matched: Vec<bool>,
+    probe: Rows<'a, K, R>,
+    /// The probe row being joined and its matches not emitted yet.
+    current: Option<(K, R, vec::IntoIter<usize>)>,
+    /// The rest of the block nested loop join the left rows are a block of.
+    rest: Option<NestedLoop<'a, K, L>>,
+}
+
+/// The left rows of a block nested loop join that are not joined yet, and the
+/// right rows every block of them is probed by.
+struct NestedLoop<'a, K, L> {
+    left: Rows<'a, K, L>,
+    right: SpillHeap,
+}
+
+/// A partition of one input.
+struct Spilled {
+    heap: Option<SpillHeap>,
+    rows: usize,
+    /// Whether partitioning it again could not make it fit.
+    stuck: bool,
+}
+
+/// A pair of spilled partitions to join, with the number of rows on each side.
+struct Partitions {
+    level: u32,
+    left: SpillHeap,
+    right: Option<SpillHeap>,
+    left_rows: usize,
+    right_rows: usize,
+    /// Whether partitioning the pair again could not make a side fit.
+    stuck: bool,
+}
+
+impl<'a, K: Hash + Eq + Clone, L: Clone, R: Clone> ProbeRight<'a, K, L, R> {
+    fn next(&mut self, join_type: JoinType) -> Option<io::Result<(K, L, Option<R>)>> {
+        loop {
+            if let Some((key, value, matches)) = &mut self.current {
+                if let Some(idx) = matches.next() {
+                    return Some(Ok((key.clone(), self.left[idx].1.clone(), Some(value.clone()))));
+                }
+                self.current = None;
+            }
+
+            let (key, value) = match self.probe.next()? {
+                Ok(row) => row,
+                Err(e) => return Some(Err(e)),
+            };
+            let matches = self.table.get(&key);
+            for idx in &matches {
+                self.matched[*idx] = true;
+            }
+            if join_type != JoinType::Semi {
+                self.current = Some((key, value, matches.into_iter()));
+            }
+        }
+    }
+
+    fn finish(self, join_type: JoinType) -> State<'a, K, L, R> {
+        let emitted = self.left.into_iter().zip(self.matched).filter(|(_, matched)| match join_type {
+            JoinType::Inner => false,
+            JoinType::LeftOuter => !matched,
+            JoinType::Semi => *matched,
+        });
+        State::FinishLeft(emitted.map(|(row, _)| row).collect::<Vec<_>>().into_iter(), self.rest)
+    }
+}
+
+/// A table built on the right rows and probed by the left ones.
+struct ProbeLeft<'a, K, L, R> {
+    right: Vec<(K, R)>,
+    table: HashTable<K, usize>,
+    probe: Rows<'a, K, L>,
+    /// The probe row being joined and its matches not emitted yet.
+    current: Option<(K, L, vec::IntoIter<usize>)>,
+}
+
+impl<'a, K: Hash + Eq + Clone, L: Clone, R: Clone> ProbeLeft<'a, K, L, R> {
+    fn next(&mut self, join_type: JoinType) -> Option<io::Result<(K, L, Option<R>)>> {
+        loop {
+            if let Some((key, value, matches)) = &mut self.current {
+                if let Some(idx) = matches.next() {
+                    return Some(Ok((key.clone(), value.clone(), Some(self.right[idx].1.clone()))));
+                }
+                self.current = None;
+            }
+
+            let (key, value) = match self.probe.next()? {
+                Ok(row) => row,
+                Err(e) => return Some(Err(e)),
+            };
+            let matches = self.table.get(&key);
+            match join_type {
+                JoinType::Semi if !matches.is_empty() => return Some(Ok((key, value, None))),
+                JoinType::LeftOuter if matches.is_empty() => return Some(Ok((key, value, None))),
+                JoinType::Inner | JoinType::LeftOuter => self.current = Some((key, value, matches.into_iter())),
+                JoinType::Semi => {}
+            }
+        }
+    }
+}
+
+/// The rows of a `HashJoin`, joined as they are read. Spilled partitions are
+/// joined when the rows before them have been read.
+pub struct HashJoinIter<'a, K, L, R> {
+    join: HashJoin,
+    state: State<'a, K, L, R>,
+    /// The spilled partition pairs not joined yet.
+    partitions: Vec<Partitions>,
+}
+
+impl<'a, K, L, R> HashJoinIter<'a, K, L, R>
+where
+    K: Hash + Eq + Clone + Codec + 'a,
+    L: Clone + Codec + 'a,
+    R: Clone + Codec + 'a,
+{
+    /// Reads both inputs in turn, so that the first one to end is the smaller,
+    /// until one ends within the limit and can be built on. Partitions both if
+    /// neither does.
+    fn load(
+        &mut self,
+        level: u32,
+        mut left: Rows<'a, K, L>,
+        mut right: Rows<'a, K, R>,
+    ) -> io::Result<State<'a, K, L, R>> {
+        let limit = self.join.max_build_rows;
+        let mut left_rows = vec![];
+        let mut right_rows = vec![];
+        loop {
+            if left_rows.len() <= limit {
+                match left.next().transpose()? {
+                    Some(row) => left_rows.push(row),
+                    None => {
+                        let probe = Box::new(right_rows.into_iter().map(Ok).chain(right));
+                        return build_left(left_rows, probe, None);
+                    }
+                }
+            }
+            if right_rows.len() <= limit {
+                match right.next().transpose()? {
+                    Some(row) => right_rows.push(row),
+                    None => {
+                        let probe = Box::new(left_rows.into_iter().map(Ok).chain(left));
+                        return build_right(right_rows, probe);
+                    }
+                }
+            }
+            if left_rows.len() > limit && right_rows.len() > limit {
+                self.partition(
+                    level,
+                    left_rows.into_iter().map(Ok).chain(left),
+                    right_rows.into_iter().map(Ok).chain(right),
+                )?;
+                return Ok(State::Done);
+            }
+        }
+    }
+
+    /// Spills both inputs and queues the pairs of partitions that can join
+    /// any rows.
+    fn partition<I, J>(&mut self, level: u32, left: I, right: J) -> io::Result<()>
+    where
+        I: Iterator<Item = io::Result<(K, L)>>,
+        J: Iterator<Item = io::Result<(K, R)>>,
+    {
+        let left = self.spill_all(level, left)?;
+        let right = self.spill_all(level, right)?;
+
+        // Partitions without a counterpart are dropped, which frees them.
+        let outer = self.join.join_type == JoinType::LeftOuter;
+        for (left, right) in left.into_iter().zip(right) {
+            if let Some(heap) = left.heap.filter(|_| right.heap.is_some() || outer) {
+                self.partitions.push(Partitions {
+                    level: level + 1,
+                    left: heap,
+                    right: right.heap,
+                    left_rows: left.rows,
+                    right_rows: right.rows,
+                    stuck: left.stuck || right.stuck,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Spills the rows of one input to partitions. A partition could not fit
+    /// when partitioned again if it has all of the rows, or if a key has more
+    /// rows than fit on its own. The key is found by a majority vote, whose
+    /// votes never exceed the rows of the key.
+    fn spill_all<T: Codec>(
+        &self,
+        level: u32,
+        rows: impl Iterator<Item = io::Result<(K, T)>>,
+    ) -> io::Result<Vec<Spilled>> {
+        let mut partitions: Vec<_> = (0..SPILL_FANOUT).map(|_| None).collect();
+        let mut counts = vec![0; SPILL_FANOUT];
+        let mut votes: Vec<Option<(K, usize)>> = vec![None; SPILL_FANOUT];
+        for row in rows {
+            let (key, value) = row?;
+            let idx = spill(&self.join.pool, &mut partitions, level, &key, &value)?;
+            counts[idx] += 1;
+            match &mut votes[idx] {
+                Some((candidate, votes)) if *candidate == key => *votes += 1,
+                Some((_, votes)) if *votes > 0 => *votes -= 1,
+                vote => *vote = Some((key, 1)),
+            }
+        }
+
+        let total: usize = counts.iter().sum();
+        let limit = self.join.max_build_rows;
+        let spilled = partitions.into_iter().zip(counts).zip(votes).map(|((heap, rows), vote)| {
+            let heavy_key = vote.map_or(false, |(_, votes)| votes > limit);
+            Spilled { heap, rows, stuck: heavy_key || rows == total }
+        });
+        Ok(spilled.collect())
+    }
+
+    /// Moves on from a state without rows left.
+    fn advance(&mut self, state: State<'a, K, L, R>) -> io::Result<Option<State<'a, K, L, R>>> {
+        match state {
+            State::Load { level, left, right } => self.load(level, left, right).map(Some),
+            State::ProbeRight(probe) => Ok(Some(probe.finish(self.join.join_type))),
+            State::FinishLeft(_, Some(nested)) => self.next_block(nested).map(Some),
+            State::FinishLeft(_, None) | State::ProbeLeft(_) | State::Done => self.next_partitions(),
+        }
+    }
+
+    /// Starts to join the next pair of spilled partitions.
+    fn next_partitions(&mut self) -> io::Result<Option<State<'a, K, L, R>>> {
+        let pair = match self.partitions.pop() {
+            Some(pair) => pair,
+            None => return Ok(None),
+        };
+        let limit = self.join.max_build_rows;
+        let left: Rows<'a, K, L> = Box::new(SpilledRows::new(pair.left));
+        match pair.right {
+            Some(right) if pair.stuck && pair.left_rows > limit && pair.right_rows > limit => {
+                self.next_block(NestedLoop { left, right }).map(Some)
+            }
+            right => {
+                let right: Rows<'a, K, R> = Box::new(right.into_iter().flat_map(SpilledRows::new));
+                Ok(Some(State::Load { level: pair.level, left, right }))
+            }
+        }
+    }
+
+    /// Builds on the next block of left rows of a nested loop join, probed by
+    /// a scan of the right rows.
+    fn next_block(&mut self, mut nested: NestedLoop<'a, K, L>) -> io::Result<State<'a, K, L, R>> {
+        let block = nested.left.by_ref().take(self.join.max_build_rows).collect::<io::Result<Vec<_>>>()?;
+        if block.is_empty() {
+            return Ok(State::Done);
+        }
+        let probe = Box::new(SpilledRows::scan(&mut nested.right));
+        build_left(block, probe, Some(nested))
+    }
+}
+
+impl<'a, K, L, R> Iterator for HashJoinIter<'a, K, L, R>
+where
+    K: Hash + Eq + Clone + Codec + 'a,
+    L: Clone + Codec + 'a,
+    R: Clone + Codec + 'a,
+{
+    type Item = io::Result<(K, L, Option<R>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let row = match &mut self.state {
+                State::ProbeRight(probe) => probe.next(self.join.join_type),
+                State::ProbeLeft(probe) => probe.next(self.join.join_type),
+                State::FinishLeft(rows, _) => rows.next().map(|(key, value)| Ok((key, value, None))),
+                State::Load { .. } | State::Done => None,
+            };
+
+            let error = match row {
+                Some(Ok(row)) => return Some(Ok(row)),
+                Some(Err(e)) => e,
+                None => {
+                    let state = mem::replace(&mut self.state, State::Done);
+                    match self.advance(state) {
+                        Ok(Some(state)) => {
+                            self.state = state;
+                            continue;
+                        }
+                        Ok(None) => return None,
+                        Err(e) => e,
+                    }
+                }
+            };
+            // Drops the rest of the join, which frees the partitions, and ends
+            // the iterator with the error.
+            self.state = State::Done;
+            self.partitions.clear();
+            return Some(Err(error));
+        }
+    }
+}
+
+fn build_left<'a, K, L, R>(
+    left: Vec<(K, L)>,
+    probe: Rows<'a, K, R>,
+    rest: Option<NestedLoop<'a, K, L>>,
+) -> io::Result<State<'a, K, L, R>>
+where
+    K: Hash + Eq + Clone,
+{
+    let table = build(&left)?;
+    let matched = vec![false; left.len()];
+    Ok(State::ProbeRight(ProbeRight { left, table, matched, probe, current: None, rest }))
+}
+
+fn build_right<K, L, R>(right: Vec<(K, R)>, probe: Rows<'_, K, L>) -> io::Result<State<'_, K, L, R>>
+where
+    K: Hash + Eq + Clone,
+{
+    let table = build(&right)?;
+    Ok(State::ProbeLeft(ProbeLeft { right, table, probe, current: None }))
+}
+
+/// Maps the keys of the rows to their indices.
+fn build<K: Hash + Eq + Clone, T>(rows: &[(K, T)]) -> io::Result<HashTable<K, usize>> {
+    let mut table = HashTable::new(64);
+    for (idx, (key, _)) in rows.iter().enumerate() {
+        table.put(key.clone(), idx).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::cmp::max;
+    use std::io;
+
+    use crate::buffer::buffer_pool_instance::BufferPoolInstance;
+    use crate::buffer::disk_manager::DiskManager;
+    use crate::buffer::test_utils::TempFile;
+
+    use super::{HashJoin, HashJoinIter, JoinType, State};
+
+    /// Joins with nested loops.
+    fn expected(join_type: JoinType, left: &[(u32, u32)], right: &[(u32, i64)]) -> Vec<(u32, u32, Option<i64>)> {
+        let mut output = vec![];
+        for (key, value) in left {
+            let matches: Vec<_> = right.iter().filter(|(k, _)| k == key).map(|(_, v)| *v).collect();
+            match join_type {
+                JoinType::Inner => output.extend(matches.iter().map(|v| (*key, *value, Some(*v)))),
+                JoinType::LeftOuter if matches.is_empty() => output.push((*key, *value, None)),
+                JoinType::LeftOuter => output.extend(matches.iter().map(|v| (*key, *value, Some(*v)))),
+                JoinType::Semi if !matches.is_empty() => output.push((*key, *value, None)),
+                JoinType::Semi => {}
+            }
+        }
+        output.sort_unstable();
+        output
+    }
+
+    fn join(
+        pool: &BufferPoolInstance,
+        join_type: JoinType,
+        max_build_rows: usize,
+        left: &[(u32, u32)],
+        right: &[(u32, i64)],
+    ) -> io::Result<Vec<(u32, u32, Option<i64>)>> {
+        let join = HashJoin::new(pool.clone(), join_type, max_build_rows);
+        let mut output = join.join(left.to_vec(), right.to_vec()).collect::<io::Result<Vec<_>>>()?;
+        output.sort_unstable();
+        Ok(output)
+    }
+
+    #[test]
+    fn should_join_in_memory() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 4);
+        let left = [(1, 10), (2, 20), (2, 21), (3, 30)];
+        let right = [(2, -2), (3, -3), (3, -4), (4, -5)];
+
+        assert_eq!(
+            join(&pool, JoinType::Inner, 10, &left, &right)?,
+            vec![(2, 20, Some(-2)), (2, 21, Some(-2)), (3, 30, Some(-4)), (3, 30, Some(-3))]
+        );
+        assert_eq!(
+            join(&pool, JoinType::LeftOuter, 10, &left, &right)?,
+            vec![(1, 10, None), (2, 20, Some(-2)), (2, 21, Some(-2)), (3, 30, Some(-4)), (3, 30, Some(-3))]
+        );
+        assert_eq!(join(&pool, JoinType::Semi, 10, &left, &right)?, vec![(2, 20, None), (2, 21, None), (3, 30, None)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_build_on_either_side() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 4);
+        let small: Vec<(u32, u32)> = (0..5).map(|i| (i * 3, i)).collect();
+        let large: Vec<(u32, i64)> = (0..40).map(|i| (i % 20, i as i64)).collect();
+        let large_left: Vec<(u32, u32)> = large.iter().map(|(k, v)| (*k, *v as u32)).collect();
+        let small_right: Vec<(u32, i64)> = small.iter().map(|(k, v)| (*k, *v as i64)).collect();
+
+        for join_type in [JoinType::Inner, JoinType::LeftOuter, JoinType::Semi] {
+            assert_eq!(join(&pool, join_type, 100, &small, &large)?, expected(join_type, &small, &large));
+            assert_eq!(
+                join(&pool, join_type, 100, &large_left, &small_right)?,
+                expected(join_type, &large_left, &small_right)
+            );
+            // Only the small side fits, so it is the build side either way.
+            assert_eq!(join(&pool, join_type, 10, &small, &large)?, expected(join_type, &small, &large));
+            assert_eq!(
+                join(&pool, join_type, 10, &large_left, &small_right)?,
+                expected(join_type, &large_left, &small_right)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn should_partition_both_sides_and_recurse() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 8);
+        let left: Vec<(u32, u32)> = (0..600).map(|i| ((i * 7) % 400, i)).collect();
+        let right: Vec<(u32, i64)> = (0..900).map(|i| ((i * 11) % 500, -(i as i64))).collect();
+
+        for join_type in [JoinType::Inner, JoinType::LeftOuter, JoinType::Semi] {
+            assert_eq!(join(&pool, join_type, 4, &left, &right)?, expected(join_type, &left, &right));
+        }
+        assert!(pool.dump_pinned().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_join_skewed_key_that_never_fits() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 8);
+        let left: Vec<(u32, u32)> = (0..30).map(|i| (7, i)).collect();
+        let right: Vec<(u32, i64)> = (0..40).map(|i| (7, i)).collect();
+
+        let output = join(&pool, JoinType::Inner, 4, &left, &right)?;
+        assert_eq!(output.len(), 30 * 40);
+        assert_eq!(output, expected(JoinType::Inner, &left, &right));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_stream_probe_side_past_small_build_side() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 4);
+        let left: Vec<(u32, u32)> = (0..5).map(|i| (i, i)).collect();
+        let pulled = Cell::new(0);
+        let right = (0..2000).map(|i| (i % 5, -(i as i64))).inspect(|_| pulled.set(pulled.get() + 1));
+
+        let mut rows = HashJoin::new(pool, JoinType::Inner, 10).join(left, right);
+        let (key, value, right_value) = rows.next().unwrap()?;
+        assert_eq!(key, value);
+        assert_eq!(right_value.map(|v| (-v) as u32 % 5), Some(key));
+        // Neither input was partitioned, and the right one is only read up to
+        // the first match.
+        assert!(pulled.get() <= 11);
+
+        assert_eq!(rows.count(), 1999);
+        assert_eq!(pulled.get(), 2000);
+
+        Ok(())
+    }
+
+    /// The rows the join has built a table on.
+    fn build_rows<K, L, R>(rows: &HashJoinIter<K, L, R>) -> usize {
+        match &rows.state {
+            State::ProbeRight(probe) => probe.left.len(),
+            State::ProbeLeft(probe) => probe.right.len(),
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn should_join_hot_key_in_blocks() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 8);
+        let left: Vec<(u32, u32)> = (0..300).map(|i| (if i % 10 == 0 { i } else { 7 }, i)).collect();
+        let right: Vec<(u32, i64)> = (0..200).map(|i| (if i % 10 == 0 { i } else { 7 }, -(i as i64))).collect();
+
+        for join_type in [JoinType::Inner, JoinType::LeftOuter, JoinType::Semi] {
+            let mut rows = HashJoin::new(pool.clone(), join_type, 16).join(left.clone(), right.clone());
+            let mut output = vec![];
+            let mut level = 0;
+            loop {
+                assert!(build_rows(&rows) <= 16);
+                level = max(level, rows.partitions.iter().map(|pair| pair.level).max().unwrap_or(0));
+                match rows.next() {
+                    Some(row) => output.push(row?),
+                    None => break,
+                }
+            }
+            output.sort_unstable();
+            assert_eq!(output, expected(join_type, &left, &right));
+            // The hot key is only partitioned once.
+            assert_eq!(level, 1);
+        }
+        assert!(pool.dump_pinned().is_empty());
+
+        Ok(())
+    }
+}
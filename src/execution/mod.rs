@@ -0,0 +1,114 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Cursor};
+use std::marker::PhantomData;
+
+use crate::buffer::buffer_pool_instance::BufferPoolInstance;
+use crate::hash_table::Codec;
+use crate::storage::table_heap::{TableHeap, TableIterator};
+
+mod hash_aggregate;
+mod hash_join;
+
+/// The number of partitions an operator spills the rows that don't fit in
+/// memory to.
+const SPILL_FANOUT: usize = 8;
+
+/// Picks the partition of a key with a hash seeded by the level, so that the
+/// rows of a partition spread over all partitions when it spills again.
+fn partition<K: Hash>(key: &K, level: u32) -> usize {
+    let mut hasher = DefaultHasher::new();
+    level.hash(&mut hasher);
+    key.hash(&mut hasher);
+    hasher.finish() as usize % SPILL_FANOUT
+}
+
+/// A temporary table heap of spilled rows. It is freed when dropped, so the
+/// pages are released even if an operator fails or is dropped before it has
+/// read its partitions back.
+struct SpillHeap(Option<TableHeap>);
+
+impl SpillHeap {
+    fn create(pool: &BufferPoolInstance) -> io::Result<Self> {
+        TableHeap::create(pool.clone()).map(|heap| SpillHeap(Some(heap)))
+    }
+
+    fn heap(&mut self) -> &mut TableHeap {
+        self.0.as_mut().expect("the heap is only taken when freed")
+    }
+
+    fn free(mut self) -> io::Result<()> {
+        self.0.take().map_or(Ok(()), TableHeap::free)
+    }
+}
+
+impl Drop for SpillHeap {
+    fn drop(&mut self) {
+        if let Some(heap) = self.0.take() {
+            // Nothing can report the error here. The pages that could not be
+            // freed stay allocated, as they would without the guard.
+            let _ = heap.free();
+        }
+    }
+}
+
+/// Appends the row to the temporary heap of its partition, creating the heap
+/// on the first row. Returns the partition.
+fn spill<K: Hash + Codec, V: Codec>(
+    pool: &BufferPoolInstance,
+    partitions: &mut [Option<SpillHeap>],
+    level: u32,
+    key: &K,
+    value: &V,
+) -> io::Result<usize> {
+    let idx = partition(key, level);
+    let heap = match &mut partitions[idx] {
+        Some(heap) => heap,
+        empty => empty.insert(SpillHeap::create(pool)?),
+    };
+    let mut row = vec![];
+    key.encode(&mut row)?;
+    value.encode(&mut row)?;
+    heap.heap().insert_tuple(&row).map(|_| idx)
+}
+
+/// Reads back the rows spilled to a heap one at a time. The heap is freed after
+/// the last one, unless it is only scanned.
+struct SpilledRows<K, V> {
+    rows: TableIterator,
+    heap: Option<SpillHeap>,
+    row: PhantomData<(K, V)>,
+}
+
+impl<K, V> SpilledRows<K, V> {
+    fn new(mut heap: SpillHeap) -> Self {
+        SpilledRows { rows: heap.heap().iter(), heap: Some(heap), row: PhantomData }
+    }
+
+    /// Reads the rows without freeing the heap, so that it can be read again.
+    fn scan(heap: &mut SpillHeap) -> Self {
+        SpilledRows { rows: heap.heap().iter(), heap: None, row: PhantomData }
+    }
+}
+
+impl<K: Codec, V: Codec> Iterator for SpilledRows<K, V> {
+    type Item = io::Result<(K, V)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.rows.next() {
+            Some(row) => Some(row.and_then(|(_, row)| {
+                let mut cursor = Cursor::new(row);
+                Ok((K::decode(&mut cursor)?, V::decode(&mut cursor)?))
+            })),
+            None => self.heap.take()?.free().err().map(Err),
+        }
+    }
+}
+
+/// Reads back the rows spilled to the heap and frees it.
+fn read_spilled<K: Codec, V: Codec, F>(heap: SpillHeap, mut f: F) -> io::Result<()>
+where
+    F: FnMut(K, V) -> io::Result<()>,
+{
+    SpilledRows::new(heap).try_for_each(|row| row.and_then(|(key, value)| f(key, value)))
+}
@@ -0,0 +1,1371 @@
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
+use std::marker::PhantomData;
+
+pub use self::snapshot::Codec;
+
+mod snapshot;
+
+/// Hashes an integer key to its own value, so that tests can choose the
+/// directory slot of every key. Other keys mix every byte and integer they
+/// write into the hash.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IdentityHasher {
+    hash: u64,
+    written: bool,
+}
+
+pub type BuildIdentityHasher = BuildHasherDefault<IdentityHasher>;
+
+impl IdentityHasher {
+    const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+    /// Keeps the integer as is when it is the first write.
+    fn fold(&mut self, i: u64) {
+        self.hash = if self.written { (self.hash.rotate_left(5) ^ i).wrapping_mul(Self::SEED) } else { i };
+        self.written = true;
+    }
+}
+
+impl Hasher for IdentityHasher {
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.fold(*byte as u64);
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.fold(i as u64);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.fold(i as u64);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.fold(i as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.fold(i);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.fold(i as u64);
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Tuple<K, V> {
+    /// The hash of the key, so that splits don't hash the key again.
+    hash: u32,
+    key: K,
+    value: V,
+}
+
+#[derive(Debug, Clone)]
+struct Bucket<K, V> {
+    depth: u32,
+    tuples: Vec<Tuple<K, V>>,
+    /// Tuples that do not fit into a full bucket because they all have the
+    /// same hash, so no split could separate them. Every overflow bucket but
+    /// the last is full.
+    overflow: Vec<Vec<Tuple<K, V>>>,
+    /// The hash of all the tuples of the bucket and its overflow buckets, if
+    /// they share one.
+    shared_hash: Option<u32>,
+}
+
+impl<K: Eq, V> Bucket<K, V> {
+    fn new() -> Self {
+        Bucket { depth: 1, tuples: Vec::new(), overflow: Vec::new(), shared_hash: None }
+    }
+
+    fn get(&self, key: &K) -> Vec<V>
+    where
+        V: Clone,
+    {
+        self.tuples().filter(|tuple| tuple.key == *key).map(|tuple| tuple.value.clone()).collect()
+    }
+
+    /// The tuples of the bucket followed by those of its overflow buckets.
+    fn tuples(&self) -> impl Iterator<Item = &Tuple<K, V>> {
+        self.tuples.iter().chain(self.overflow.iter().flatten())
+    }
+
+    fn tuples_mut(&mut self) -> impl Iterator<Item = &mut Tuple<K, V>> {
+        self.tuples.iter_mut().chain(self.overflow.iter_mut().flatten())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tuples.is_empty()
+    }
+
+    fn is_full(&self, bucket_size_limit: usize) -> bool {
+        self.tuples.len() >= bucket_size_limit
+    }
+
+    /// Whether a tuple with the hash can be put without splitting the bucket,
+    /// because the bucket has room or the tuple goes to its overflow buckets.
+    fn fits(&self, hash: u32, bucket_size_limit: usize) -> bool {
+        !self.is_full(bucket_size_limit) || self.shared_hash == Some(hash)
+    }
+
+    /// Appends the tuple to the bucket, or to its last overflow bucket if the
+    /// bucket is full, attaching a new overflow bucket if that one is full too.
+    fn put_overflow(&mut self, hash: u32, key: K, value: V, bucket_size_limit: usize) -> &mut V {
+        self.shared_hash = if self.is_empty() { Some(hash) } else { self.shared_hash.filter(|shared| *shared == hash) };
+        let tuples = if !self.is_full(bucket_size_limit) {
+            &mut self.tuples
+        } else {
+            if self.overflow.last().map_or(true, |last| last.len() >= bucket_size_limit) {
+                self.overflow.push(Vec::new());
+            }
+            self.overflow.last_mut().unwrap()
+        };
+        tuples.push(Tuple { hash, key, value });
+        &mut tuples.last_mut().unwrap().value
+    }
+
+    /// Removes the tuples of the bucket and its overflow buckets.
+    fn take_tuples(&mut self) -> Vec<Tuple<K, V>> {
+        let mut tuples = std::mem::take(&mut self.tuples);
+        for mut overflow in self.overflow.drain(..) {
+            tuples.append(&mut overflow);
+        }
+        self.shared_hash = None;
+        tuples
+    }
+
+    /// Replaces the tuples of the bucket, filling overflow buckets with those
+    /// beyond the limit.
+    fn set_tuples(&mut self, mut tuples: Vec<Tuple<K, V>>, bucket_size_limit: usize) {
+        self.shared_hash = tuples.first().map(|first| first.hash).filter(|hash| tuples.iter().all(|t| t.hash == *hash));
+        let mut rest = tuples.split_off(tuples.len().min(bucket_size_limit)).into_iter().peekable();
+        self.tuples = tuples;
+        self.overflow.clear();
+        while rest.peek().is_some() {
+            self.overflow.push(rest.by_ref().take(bucket_size_limit).collect());
+        }
+    }
+}
+
+/// The largest global depth of a `HashTable` unless set otherwise.
+pub(crate) const DEFAULT_MAX_DEPTH: u32 = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashTableError {
+    /// A bucket has to split to take a tuple, but the directory already has
+    /// the maximum depth.
+    DirectoryFull { max_depth: u32 },
+}
+
+impl fmt::Display for HashTableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashTableError::DirectoryFull { max_depth } => {
+                write!(f, "the directory cannot grow beyond depth {}", max_depth)
+            }
+        }
+    }
+}
+
+impl Error for HashTableError {}
+
+/// An extendible hash table that keeps every value put under a key. The
+/// directory is indexed by the least significant bits of the key hashes, so
+/// its size follows the number of tuples rather than the pattern of the keys.
+/// Keys are hashed with SipHash by default; `BuildIdentityHasher` makes the
+/// placement predictable in tests.
+///
+/// Tuples that share a hash cannot be separated by splitting, so once they
+/// fill a bucket the rest go to overflow buckets chained to it. The global
+/// depth is capped, and `put` fails rather than growing the directory past it.
+///
+/// A table created with `new_unique` or `with_hasher_unique` is a map instead:
+/// `insert` replaces the value of a key and `get` returns the only one.
+#[derive(Clone, Debug)]
+pub(crate) struct HashTable<K, V, S = RandomState, M = Multi> {
+    depth: u32,
+    max_depth: u32,
+    /// Indexes into `buckets`. A bucket of local depth `d` is pointed to by
+    /// the `2^(depth - d)` slots that share its `d` bits.
+    directories: Vec<usize>,
+    buckets: Vec<Bucket<K, V>>,
+    len: usize,
+    bucket_size_limit: usize,
+    hash_builder: S,
+    mode: PhantomData<M>,
+}
+
+/// How a `HashTable` treats a key that is put again.
+pub trait KeyMode {
+    const UNIQUE: bool;
+}
+
+/// Every value put under a key is kept, in the order they were put.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Multi;
+
+/// A key has at most one value, replaced by every insert.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Unique;
+
+impl KeyMode for Multi {
+    const UNIQUE: bool = false;
+}
+
+impl KeyMode for Unique {
+    const UNIQUE: bool = true;
+}
+
+impl<K: Hash + Eq, V> HashTable<K, V, RandomState, Multi> {
+    pub(crate) fn new(bucket_size_limit: usize) -> Self {
+        HashTable::with_hasher(bucket_size_limit, RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq, V> HashTable<K, V, RandomState, Unique> {
+    pub(crate) fn new_unique(bucket_size_limit: usize) -> Self {
+        HashTable::with_hasher_unique(bucket_size_limit, RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> HashTable<K, V, S, Multi> {
+    pub(crate) fn with_hasher(bucket_size_limit: usize, hash_builder: S) -> Self {
+        HashTable::with_mode(bucket_size_limit, hash_builder)
+    }
+
+    pub(crate) fn get(&self, key: &K) -> Vec<V>
+    where
+        V: Clone,
+    {
+        self.buckets[self.directories[self.idx(key) as usize]].get(key)
+    }
+
+    /// Appends the value to the values of the key. Fails if the bucket of the
+    /// key has to split and the directory is already at its maximum depth.
+    pub(crate) fn put(&mut self, key: K, value: V) -> Result<(), HashTableError> {
+        self.upsert(key, value).map(|_| ())
+    }
+
+    /// Removes the key and returns all of its values.
+    pub(crate) fn remove(&mut self, key: &K) -> Vec<V> {
+        self.take_values(key)
+    }
+
+    /// Removes one occurrence of the value from the values of the key.
+    pub(crate) fn remove_value(&mut self, key: &K, value: &V) -> bool
+    where
+        V: PartialEq,
+    {
+        let idx = self.idx(key);
+        let bucket = &mut self.buckets[self.directories[idx as usize]];
+        let mut tuples = bucket.take_tuples();
+        let position = tuples.iter().position(|tuple| tuple.key == *key && tuple.value == *value);
+        if let Some(position) = position {
+            tuples.remove(position);
+        }
+        bucket.set_tuples(tuples, self.bucket_size_limit);
+
+        if position.is_some() {
+            self.len -= 1;
+            self.merge_bucket(idx);
+        }
+        position.is_some()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> HashTable<K, V, S, Unique> {
+    pub(crate) fn with_hasher_unique(bucket_size_limit: usize, hash_builder: S) -> Self {
+        HashTable::with_mode(bucket_size_limit, hash_builder)
+    }
+
+    pub(crate) fn get(&self, key: &K) -> Option<&V> {
+        let bucket = &self.buckets[self.directories[self.idx(key) as usize]];
+        bucket.tuples().find(|tuple| tuple.key == *key).map(|tuple| &tuple.value)
+    }
+
+    /// Sets the value of the key and returns the one it replaces. Fails like
+    /// `put` if the key is new and its bucket cannot split.
+    pub(crate) fn insert(&mut self, key: K, value: V) -> Result<Option<V>, HashTableError> {
+        self.upsert(key, value)
+    }
+
+    pub(crate) fn remove(&mut self, key: &K) -> Option<V> {
+        self.take_values(key).pop()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher, M: KeyMode> HashTable<K, V, S, M> {
+    fn with_mode(bucket_size_limit: usize, hash_builder: S) -> Self {
+        assert!(bucket_size_limit > 0, "a bucket must hold at least one tuple");
+        HashTable {
+            depth: 1,
+            max_depth: DEFAULT_MAX_DEPTH,
+            directories: vec![0, 1],
+            buckets: vec![Bucket::new(), Bucket::new()],
+            len: 0,
+            bucket_size_limit,
+            hash_builder,
+            mode: PhantomData,
+        }
+    }
+
+    /// Sets the largest global depth, which must be at least the current one.
+    pub(crate) fn set_max_depth(&mut self, max_depth: u32) {
+        assert!((self.depth..=32).contains(&max_depth), "invalid maximum depth {}", max_depth);
+        self.max_depth = max_depth;
+    }
+
+    /// The number of `(key, value)` pairs.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterates over every `(key, value)` pair in no particular order, except
+    /// that the values of a key come in the order they were put.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.buckets.iter().flat_map(Bucket::tuples).map(|tuple| (&tuple.key, &tuple.value))
+    }
+
+    /// Iterates over every key once, in no particular order.
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &K> {
+        // All the tuples of a key are in the same bucket, so a key is yielded
+        // at its first tuple there.
+        self.buckets.iter().flat_map(|bucket| {
+            let mut seen = HashSet::new();
+            bucket.tuples().map(|tuple| &tuple.key).filter(move |key| seen.insert(*key))
+        })
+    }
+
+    /// Gets the first value of the key for in-place manipulation, or the place
+    /// to put one, hashing the key only once.
+    pub(crate) fn entry(&mut self, key: K) -> Entry<'_, K, V, S, M> {
+        let hash = self.hash(&key);
+        let bucket = self.directories[mask(hash, self.depth) as usize];
+        let position = self.buckets[bucket].tuples().position(|tuple| tuple.key == key);
+        match position {
+            Some(position) => {
+                Entry::Occupied(OccupiedEntry { tuple: self.buckets[bucket].tuples_mut().nth(position).unwrap() })
+            }
+            None => Entry::Vacant(VacantEntry { table: self, hash, key }),
+        }
+    }
+
+    /// Keeps only the tuples for which `f` returns `true`.
+    pub(crate) fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        for bucket in &mut self.buckets {
+            let mut tuples = bucket.take_tuples();
+            let size = tuples.len();
+            tuples.retain(|tuple| f(&tuple.key, &tuple.value));
+            self.len -= size - tuples.len();
+            bucket.set_tuples(tuples, self.bucket_size_limit);
+        }
+
+        let mut idx = 0;
+        while idx < self.directories.len() as u32 {
+            if self.buckets[self.directories[idx as usize]].is_empty() {
+                self.merge_bucket(idx);
+            }
+            idx += 1;
+        }
+    }
+
+    /// Puts or inserts every pair like `extend`, but stops at the first pair
+    /// that does not fit instead of panicking. The pairs before it stay put.
+    pub(crate) fn try_extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) -> Result<(), HashTableError> {
+        for (key, value) in iter {
+            self.upsert(key, value)?;
+        }
+        Ok(())
+    }
+
+    /// Removes every tuple and returns them in no particular order. The table
+    /// is left as it was created.
+    pub(crate) fn drain(&mut self) -> impl Iterator<Item = (K, V)> {
+        let buckets = std::mem::replace(&mut self.buckets, vec![Bucket::new(), Bucket::new()]);
+        self.directories = vec![0, 1];
+        self.depth = 1;
+        self.len = 0;
+        buckets.into_iter().flat_map(|mut bucket| bucket.take_tuples()).map(|tuple| (tuple.key, tuple.value))
+    }
+
+    /// Removes the key and returns all of its values.
+    fn take_values(&mut self, key: &K) -> Vec<V> {
+        let idx = self.idx(key);
+        let bucket = &mut self.buckets[self.directories[idx as usize]];
+        let (removed, kept): (Vec<_>, Vec<_>) = bucket.take_tuples().into_iter().partition(|tuple| tuple.key == *key);
+        bucket.set_tuples(kept, self.bucket_size_limit);
+
+        if !removed.is_empty() {
+            self.len -= removed.len();
+            self.merge_bucket(idx);
+        }
+        removed.into_iter().map(|tuple| tuple.value).collect()
+    }
+
+    /// Replaces the value of the key in unique mode, and appends it otherwise.
+    fn upsert(&mut self, key: K, value: V) -> Result<Option<V>, HashTableError> {
+        let hash = self.hash(&key);
+        if M::UNIQUE {
+            let bucket = self.directories[mask(hash, self.depth) as usize];
+            let position = self.buckets[bucket].tuples().position(|tuple| tuple.key == key);
+            if let Some(position) = position {
+                let tuple = self.buckets[bucket].tuples_mut().nth(position).unwrap();
+                return Ok(Some(std::mem::replace(&mut tuple.value, value)));
+            }
+        }
+        self.put_hashed(hash, key, value).map(|_| None)
+    }
+
+    fn put_hashed(&mut self, hash: u32, key: K, value: V) -> Result<&mut V, HashTableError> {
+        loop {
+            let idx = mask(hash, self.depth);
+            let bucket = &self.buckets[self.directories[idx as usize]];
+            if bucket.fits(hash, self.bucket_size_limit) {
+                self.len += 1;
+                let bucket = &mut self.buckets[self.directories[idx as usize]];
+                return Ok(bucket.put_overflow(hash, key, value, self.bucket_size_limit));
+            }
+
+            if bucket.depth == self.depth {
+                if self.depth == self.max_depth {
+                    return Err(HashTableError::DirectoryFull { max_depth: self.max_depth });
+                }
+                self.expand_directories();
+            }
+            self.split_bucket(idx);
+        }
+    }
+
+    fn expand_directories(&mut self) {
+        self.directories.extend_from_within(..);
+        self.depth += 1;
+    }
+
+    /// Moves the tuples that differ from the bucket in the next hash bit to a
+    /// new bucket, and points every directory slot of that half to it.
+    fn split_bucket(&mut self, bucket_idx: u32) {
+        let old_bucket = self.directories[bucket_idx as usize];
+        let depth = self.buckets[old_bucket].depth + 1;
+        let bucket_bits = mask(bucket_idx, depth);
+        let (keep, remove) = self.buckets[old_bucket]
+            .take_tuples()
+            .into_iter()
+            .partition(|tuple| mask(tuple.hash, depth) == bucket_bits);
+        let bucket = &mut self.buckets[old_bucket];
+        bucket.depth = depth;
+        bucket.set_tuples(keep, self.bucket_size_limit);
+
+        let mut new_idx_bucket = Bucket::new();
+        new_idx_bucket.depth = depth;
+        new_idx_bucket.set_tuples(remove, self.bucket_size_limit);
+        self.buckets.push(new_idx_bucket);
+        let new_bucket = self.buckets.len() - 1;
+
+        let image_bits = split_image_idx(bucket_bits, depth);
+        for idx in 0..self.directories.len() {
+            if mask(idx as u32, depth) == image_bits && self.directories[idx] == old_bucket {
+                self.directories[idx] = new_bucket;
+            }
+        }
+    }
+
+    /// Merges the bucket at the slot with its split image while one of them is
+    /// empty and both have the same local depth, then shrinks the directory.
+    fn merge_bucket(&mut self, mut bucket_idx: u32) {
+        loop {
+            let bucket = self.directories[bucket_idx as usize];
+            let depth = self.buckets[bucket].depth;
+            if depth <= 1 {
+                break;
+            }
+
+            let image = self.directories[split_image_idx(mask(bucket_idx, depth), depth) as usize];
+            if self.buckets[image].depth != depth
+                || (!self.buckets[bucket].is_empty() && !self.buckets[image].is_empty())
+            {
+                break;
+            }
+
+            let mut tuples = self.buckets[image].take_tuples();
+            let merged = &mut self.buckets[bucket];
+            tuples.append(&mut merged.take_tuples());
+            merged.depth -= 1;
+            merged.set_tuples(tuples, self.bucket_size_limit);
+            for slot in self.directories.iter_mut().filter(|slot| **slot == image) {
+                *slot = bucket;
+            }
+            self.free_bucket(image);
+            bucket_idx = mask(bucket_idx, depth - 1);
+        }
+
+        self.shrink_directories();
+    }
+
+    /// Drops a bucket that no slot points to, moving the last bucket into its
+    /// place.
+    fn free_bucket(&mut self, bucket: usize) {
+        let last = self.buckets.len() - 1;
+        self.buckets.swap_remove(bucket);
+        for slot in self.directories.iter_mut().filter(|slot| **slot == last) {
+            *slot = bucket;
+        }
+    }
+
+    /// Halves the directory while no bucket uses all of its bits.
+    fn shrink_directories(&mut self) {
+        while self.depth > 1 && self.buckets.iter().all(|bucket| bucket.depth < self.depth) {
+            self.directories.truncate(self.directories.len() / 2);
+            self.depth -= 1;
+        }
+    }
+
+    /// Checks the invariants of extendible hashing and returns the first
+    /// violation found.
+    pub(crate) fn verify(&self) -> Result<(), String> {
+        if self.directories.len() != 1 << self.depth || self.depth > self.max_depth {
+            return Err(format!(
+                "directory of depth {} (max {}) has {} slots",
+                self.depth,
+                self.max_depth,
+                self.directories.len()
+            ));
+        }
+
+        let mut slots = vec![0usize; self.buckets.len()];
+        for (idx, bucket) in self.directories.iter().enumerate() {
+            let depth = match self.buckets.get(*bucket) {
+                Some(bucket) => bucket.depth,
+                None => return Err(format!("slot {} points to missing bucket {}", idx, bucket)),
+            };
+            let canonical = self.directories[mask(idx as u32, depth) as usize];
+            if canonical != *bucket {
+                return Err(format!(
+                    "slot {} points to bucket {} but its {} bits to {}",
+                    idx, bucket, depth, canonical
+                ));
+            }
+            slots[*bucket] += 1;
+        }
+
+        let mut len = 0;
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            if !(1..=self.depth).contains(&bucket.depth) {
+                return Err(format!(
+                    "bucket {} has local depth {} in a directory of depth {}",
+                    i, bucket.depth, self.depth
+                ));
+            }
+            if slots[i] != 1 << (self.depth - bucket.depth) {
+                return Err(format!("bucket {} of local depth {} is in {} slots", i, bucket.depth, slots[i]));
+            }
+
+            if bucket.tuples.len() > self.bucket_size_limit {
+                return Err(format!("bucket {} holds {} tuples", i, bucket.tuples.len()));
+            }
+            if !bucket.overflow.is_empty() && !bucket.is_full(self.bucket_size_limit) {
+                return Err(format!("bucket {} has an overflow bucket but is not full", i));
+            }
+            let last = bucket.overflow.len().saturating_sub(1);
+            for (position, overflow) in bucket.overflow.iter().enumerate() {
+                if overflow.is_empty() || overflow.len() > self.bucket_size_limit {
+                    return Err(format!("an overflow bucket of bucket {} holds {} tuples", i, overflow.len()));
+                }
+                if position < last && overflow.len() < self.bucket_size_limit {
+                    return Err(format!("bucket {} has an overflow bucket that is not full before the last", i));
+                }
+            }
+
+            let first_hash = bucket.tuples().next().map(|tuple| self.hash(&tuple.key));
+            let shared_hash = first_hash.filter(|first| bucket.tuples().all(|tuple| self.hash(&tuple.key) == *first));
+            if bucket.shared_hash != shared_hash {
+                return Err(format!(
+                    "bucket {} caches the shared hash {:?} instead of {:?}",
+                    i, bucket.shared_hash, shared_hash
+                ));
+            }
+            for (position, tuple) in bucket.tuples().enumerate() {
+                let hash = self.hash(&tuple.key);
+                if self.directories[mask(hash, self.depth) as usize] != i {
+                    return Err(format!("a tuple with hash {:#x} is in bucket {}", hash, i));
+                }
+                if tuple.hash != hash {
+                    return Err(format!("a tuple with hash {:#x} in bucket {} caches {:#x}", hash, i, tuple.hash));
+                }
+                if !bucket.overflow.is_empty() && Some(hash) != first_hash {
+                    return Err(format!("bucket {} has an overflow bucket but tuples of different hashes", i));
+                }
+                if M::UNIQUE && bucket.tuples().take(position).any(|other| other.key == tuple.key) {
+                    return Err(format!("bucket {} has a duplicate key in unique mode", i));
+                }
+                len += 1;
+            }
+        }
+
+        if len != self.len {
+            return Err(format!("the table counts {} tuples but holds {}", self.len, len));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn describe(&self) -> HashTableReport {
+        let mut local_depths = BTreeMap::new();
+        for bucket in &self.buckets {
+            *local_depths.entry(bucket.depth).or_insert(0) += 1;
+        }
+        let overflow_buckets = self.buckets.iter().map(|bucket| bucket.overflow.len()).sum();
+        HashTableReport {
+            global_depth: self.depth,
+            directory_size: self.directories.len(),
+            buckets: self.buckets.len(),
+            overflow_buckets,
+            local_depths,
+            load_factor: self.len as f64 / ((self.buckets.len() + overflow_buckets) * self.bucket_size_limit) as f64,
+        }
+    }
+
+    fn idx(&self, key: &K) -> u32 {
+        mask(self.hash(key), self.depth)
+    }
+
+    fn hash(&self, key: &K) -> u32 {
+        hash_key(&self.hash_builder, key)
+    }
+}
+
+/// The shape of a `HashTable`, as returned by `describe`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct HashTableReport {
+    pub(crate) global_depth: u32,
+    pub(crate) directory_size: usize,
+    pub(crate) buckets: usize,
+    pub(crate) overflow_buckets: usize,
+    /// The number of buckets of every local depth.
+    pub(crate) local_depths: BTreeMap<u32, usize>,
+    /// The tuples divided by the capacity of all buckets, overflow included.
+    pub(crate) load_factor: f64,
+}
+
+impl fmt::Display for HashTableReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "global depth: {}", self.global_depth)?;
+        writeln!(f, "directory size: {}", self.directory_size)?;
+        writeln!(f, "buckets: {} (+{} overflow)", self.buckets, self.overflow_buckets)?;
+        writeln!(f, "load factor: {:.2}", self.load_factor)?;
+        writeln!(f, "local depths:")?;
+        for (depth, count) in &self.local_depths {
+            writeln!(f, "  {:>2} | {:<5} {}", depth, count, "#".repeat((*count).min(60)))?;
+        }
+        Ok(())
+    }
+}
+
+/// The bucket size limit of tables built with `collect`.
+const DEFAULT_BUCKET_SIZE_LIMIT: usize = 64;
+
+/// Builds a table with the default bucket size limit and maximum depth. Panics
+/// like `extend`; use `try_extend` on an empty table where the directory may
+/// fill up.
+impl<K: Hash + Eq, V, S: BuildHasher + Default, M: KeyMode> FromIterator<(K, V)> for HashTable<K, V, S, M> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut table = HashTable::with_mode(DEFAULT_BUCKET_SIZE_LIMIT, S::default());
+        table.extend(iter);
+        table
+    }
+}
+
+/// Puts or inserts every pair. Panics if the directory would have to grow past
+/// its maximum depth; `try_extend` returns the error instead.
+impl<K: Hash + Eq, V, S: BuildHasher, M: KeyMode> Extend<(K, V)> for HashTable<K, V, S, M> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        self.try_extend(iter).expect("the hash table directory is full");
+    }
+}
+
+/// The first value of a key in a `HashTable`, or the place where a value would
+/// be put if the key has none.
+pub(crate) enum Entry<'a, K, V, S, M> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V, S, M>),
+}
+
+pub(crate) struct OccupiedEntry<'a, K, V> {
+    tuple: &'a mut Tuple<K, V>,
+}
+
+pub(crate) struct VacantEntry<'a, K, V, S, M> {
+    table: &'a mut HashTable<K, V, S, M>,
+    hash: u32,
+    key: K,
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher, M: KeyMode> Entry<'a, K, V, S, M> {
+    pub(crate) fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Puts the default if the key has no value. Fails like `put`.
+    pub(crate) fn or_insert(self, default: V) -> Result<&'a mut V, HashTableError> {
+        self.or_insert_with(|| default)
+    }
+
+    pub(crate) fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> Result<&'a mut V, HashTableError> {
+        match self {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Modifies the value if the key has one.
+    pub(crate) fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    pub(crate) fn key(&self) -> &K {
+        &self.tuple.key
+    }
+
+    pub(crate) fn get(&self) -> &V {
+        &self.tuple.value
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut V {
+        &mut self.tuple.value
+    }
+
+    pub(crate) fn into_mut(self) -> &'a mut V {
+        &mut self.tuple.value
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher, M: KeyMode> VacantEntry<'a, K, V, S, M> {
+    pub(crate) fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub(crate) fn insert(self, value: V) -> Result<&'a mut V, HashTableError> {
+        self.table.put_hashed(self.hash, self.key, value)
+    }
+
+    pub(crate) fn into_key(self) -> K {
+        self.key
+    }
+}
+
+/// Folds the high half of the hash into the low one, so that no bit of the
+/// hash is lost to the `u32` the directories are indexed with.
+pub(crate) fn hash_key<K: Hash + ?Sized, S: BuildHasher>(hash_builder: &S, key: &K) -> u32 {
+    let mut hasher = hash_builder.build_hasher();
+    key.hash(&mut hasher);
+    let hash = hasher.finish();
+    (hash ^ (hash >> 32)) as u32
+}
+
+pub(crate) fn mask(bucket_idx: u32, depth: u32) -> u32 {
+    let mask = u32::checked_shl(1, depth).unwrap_or(0).wrapping_sub(1);
+    bucket_idx & mask
+}
+
+fn split_image_idx(bucket_idx: u32, depth: u32) -> u32 {
+    u32::checked_shl(1, depth - 1).map(|a| a ^ bucket_idx).unwrap_or(bucket_idx)
+}
+
+#[cfg(test)]
+mod test {
+    use std::hash::{BuildHasher, Hash, Hasher};
+
+    use super::{
+        hash_key, mask, split_image_idx, BuildIdentityHasher, HashTable, HashTableError, IdentityHasher, Unique,
+    };
+
+    #[test]
+    fn should_find_least_significant_bits() {
+        assert_eq!(mask(0b1111, 2), 0b11);
+        assert_eq!(mask(0b11, 32), 0b11);
+        assert_eq!(mask(u32::MAX, 32), u32::MAX);
+        assert_eq!(mask(0b1010, 2), 0b10);
+        assert_eq!(mask(0, 2), 0);
+        assert_eq!(mask(0b0, 2), 0);
+    }
+
+    #[test]
+    fn should_get_split_image_idx() {
+        assert_eq!(split_image_idx(0, 1), 1);
+        assert_eq!(split_image_idx(0b1, 1), 0);
+        assert_eq!(split_image_idx(0b11, 2), 0b01);
+        assert_eq!(split_image_idx(0b1111, 4), 0b0111);
+        assert_eq!(split_image_idx(0b101, 3), 0b0001);
+        assert_eq!(split_image_idx(0b0001, 3), 0b0101);
+    }
+
+    #[test]
+    fn should_hash_integers_to_themselves_and_fold_other_keys() {
+        let hash = |key: &dyn Fn(&mut IdentityHasher)| {
+            let mut hasher = BuildIdentityHasher::default().build_hasher();
+            key(&mut hasher);
+            hasher.finish()
+        };
+        let builder = BuildIdentityHasher::default();
+
+        assert_eq!(hash(&|hasher| 42u32.hash(hasher)), 42);
+        assert_eq!(hash(&|hasher| 7usize.hash(hasher)), 7);
+        assert_eq!(hash_key(&builder, &42u32), 42);
+        assert_eq!(hash_key(&builder, &7usize), 7);
+        assert_ne!(hash_key(&builder, "a"), hash_key(&builder, "b"));
+        assert_ne!(hash_key(&builder, "ab"), hash_key(&builder, "abc"));
+        assert_ne!(hash_key(&builder, "abcde"), hash_key(&builder, "ebcda"));
+        assert_ne!(hash_key(&builder, &(1u32, 5u32)), hash_key(&builder, &(2u32, 5u32)));
+        assert_ne!(hash_key(&builder, &(1u32, 2u32, 3u32)), hash_key(&builder, &(3u32, 2u32, 1u32)));
+    }
+
+    #[test]
+    fn should_spread_short_string_keys_with_identity_hasher() -> Result<(), HashTableError> {
+        let mut table: HashTable<String, u32, _> = HashTable::with_hasher(2, BuildIdentityHasher::default());
+
+        for (i, key) in ["a", "b", "c", "d", "e", "f", "g", "h"].into_iter().enumerate() {
+            table.put(key.to_string(), i as u32)?;
+        }
+
+        assert!(table.depth > 1);
+        assert_eq!(table.describe().overflow_buckets, 0);
+        assert_eq!(table.get(&"e".to_string()), vec![4]);
+        table.verify().unwrap();
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_not_find_non_existent_value() {
+        let table: HashTable<u32, u32> = HashTable::new(3);
+
+        assert!(table.get(&10).is_empty())
+    }
+
+    #[test]
+    fn should_put_get() -> Result<(), HashTableError> {
+        let mut table = HashTable::new(3);
+
+        table.put(10, 11)?;
+        assert_eq!(table.get(&10), vec![11]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_put_get_multiple_values() -> Result<(), HashTableError> {
+        let mut table = HashTable::new(3);
+
+        table.put(10, 11)?;
+        table.put(10, 12)?;
+        assert_eq!(table.get(&10), vec![11, 12]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_put_get_multiple() -> Result<(), HashTableError> {
+        let mut table = HashTable::with_hasher(3, BuildIdentityHasher::default());
+
+        table.put(16, 16)?;
+        assert_eq!(table.get(&16), vec![16]);
+
+        table.put(4, 4)?;
+        assert_eq!(table.get(&4), vec![4]);
+
+        table.put(6, 6)?;
+        assert_eq!(table.get(&6), vec![6]);
+
+        table.put(22, 22)?;
+        assert_eq!(table.get(&22), vec![22]);
+
+        table.put(24, 24)?;
+        assert_eq!(table.get(&24), vec![24]);
+
+        table.put(10, 10)?;
+        assert_eq!(table.get(&10), vec![10]);
+
+        table.put(31, 31)?;
+        assert_eq!(table.get(&31), vec![31]);
+
+        table.put(7, 7)?;
+        assert_eq!(table.get(&7), vec![7]);
+
+        table.put(9, 9)?;
+        assert_eq!(table.get(&9), vec![9]);
+
+        table.put(20, 20)?;
+        assert_eq!(table.get(&20), vec![20]);
+
+        table.put(26, 26)?;
+        assert_eq!(table.get(&26), vec![26]);
+
+        assert_eq!(table.get(&16), vec![16]);
+        assert_eq!(table.get(&4), vec![4]);
+        assert_eq!(table.get(&6), vec![6]);
+        assert_eq!(table.get(&22), vec![22]);
+        assert_eq!(table.get(&24), vec![24]);
+        assert_eq!(table.get(&10), vec![10]);
+        assert_eq!(table.get(&31), vec![31]);
+        assert_eq!(table.get(&7), vec![7]);
+        assert_eq!(table.get(&9), vec![9]);
+        assert_eq!(table.get(&20), vec![20]);
+        assert_eq!(table.get(&26), vec![26]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_put_get_many() -> Result<(), HashTableError> {
+        let mut table = HashTable::new(491);
+
+        for i in 1..10000 {
+            table.put(i, i)?;
+            table.put(i, 2 * i)?;
+            assert_eq!(table.get(&i), vec![i, 2 * i]);
+        }
+        assert_eq!(table.verify(), Ok(()));
+
+        for i in 1..10000 {
+            assert_eq!(table.get(&i), vec![i, 2 * i]);
+        }
+        assert_eq!(table.verify(), Ok(()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_put_get_string_keys() -> Result<(), HashTableError> {
+        let mut table = HashTable::new(2);
+
+        for name in ["alpha", "beta", "gamma", "delta", "epsilon"] {
+            table.put(name.to_string(), name.len())?;
+        }
+        table.put("beta".to_string(), 0)?;
+
+        assert_eq!(table.get(&"beta".to_string()), vec![4, 0]);
+        assert_eq!(table.get(&"epsilon".to_string()), vec![7]);
+        assert!(table.get(&"zeta".to_string()).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_put_get_composite_keys() -> Result<(), HashTableError> {
+        let mut table = HashTable::new(4);
+
+        for user in 0..100u64 {
+            for order in 0..3u32 {
+                table.put((user, order), format!("{}-{}", user, order))?;
+            }
+        }
+
+        assert_eq!(table.get(&(42, 1)), vec!["42-1".to_string()]);
+        assert!(table.get(&(42, 3)).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_grow_directory_with_volume_not_key_pattern() -> Result<(), HashTableError> {
+        let keys: Vec<u32> = (0..200).map(|i| i << 10).collect();
+        let mut hashed = HashTable::new(64);
+        let mut identity = HashTable::with_hasher(64, BuildIdentityHasher::default());
+
+        for key in &keys {
+            hashed.put(*key, *key)?;
+            identity.put(*key, *key)?;
+        }
+
+        assert!(hashed.depth <= 5);
+        assert!(identity.depth > 10);
+        for key in &keys {
+            assert_eq!(hashed.get(key), vec![*key]);
+            assert_eq!(identity.get(key), vec![*key]);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_remove_all_values_of_key() -> Result<(), HashTableError> {
+        let mut table = HashTable::new(3);
+
+        table.put(10, 11)?;
+        table.put(10, 12)?;
+        table.put(20, 21)?;
+
+        assert_eq!(table.remove(&10), vec![11, 12]);
+        assert!(table.get(&10).is_empty());
+        assert!(table.remove(&10).is_empty());
+        assert_eq!(table.get(&20), vec![21]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_remove_single_value() -> Result<(), HashTableError> {
+        let mut table = HashTable::new(3);
+
+        table.put(10, 11)?;
+        table.put(10, 12)?;
+        table.put(10, 11)?;
+
+        assert!(table.remove_value(&10, &11));
+        assert_eq!(table.get(&10), vec![12, 11]);
+        assert!(!table.remove_value(&10, &13));
+        assert!(!table.remove_value(&30, &11));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_retain_matching_tuples() -> Result<(), HashTableError> {
+        let mut table = HashTable::new(4);
+
+        for i in 0..100 {
+            table.put(i, i * 10)?;
+        }
+        table.retain(|key, _| key % 3 == 0);
+
+        for i in 0..100 {
+            assert_eq!(table.get(&i).is_empty(), i % 3 != 0);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_merge_buckets_and_shrink_directory() -> Result<(), HashTableError> {
+        let mut table = HashTable::with_hasher(2, BuildIdentityHasher::default());
+
+        for i in 0..16 {
+            table.put(i, i)?;
+        }
+        assert_eq!(table.depth, 3);
+        assert_eq!(table.directories.len(), 8);
+        assert_eq!(table.verify(), Ok(()));
+
+        for i in (0..16).filter(|i| i % 2 == 1) {
+            table.remove(&i);
+        }
+        for i in (0..16).filter(|i| i % 2 == 0) {
+            assert_eq!(table.get(&i), vec![i]);
+        }
+
+        for i in 0..16 {
+            table.remove(&i);
+        }
+        assert_eq!(table.depth, 1);
+        assert_eq!(table.directories.len(), 2);
+        assert_eq!(table.buckets.len(), 2);
+        assert!(table.buckets.iter().all(|bucket| bucket.depth == 1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_shrink_after_bulk_delete() -> Result<(), HashTableError> {
+        let mut table = HashTable::new(8);
+
+        for i in 0..5000 {
+            table.put(i, i)?;
+        }
+        let depth = table.depth;
+        table.retain(|key, _| *key < 10);
+
+        assert!(table.depth < depth);
+        assert_eq!(table.verify(), Ok(()));
+        for i in 0..10 {
+            assert_eq!(table.get(&i), vec![i]);
+        }
+        assert!(table.get(&10).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_chain_overflow_buckets_for_one_key() -> Result<(), HashTableError> {
+        let mut table = HashTable::with_hasher(2, BuildIdentityHasher::default());
+
+        for value in 0..100 {
+            table.put(7, value)?;
+        }
+        assert_eq!(table.depth, 1);
+        assert_eq!(table.get(&7), (0..100).collect::<Vec<_>>());
+
+        table.put(9, 9)?;
+        assert_eq!(table.depth, 2);
+        assert_eq!(table.get(&9), vec![9]);
+        assert_eq!(table.verify(), Ok(()));
+        assert_eq!(table.get(&7).len(), 100);
+
+        assert!(table.remove_value(&7, &0));
+        assert_eq!(table.get(&7), (1..100).collect::<Vec<_>>());
+        assert_eq!(table.remove(&7).len(), 99);
+        assert_eq!(table.get(&9), vec![9]);
+        assert_eq!(table.verify(), Ok(()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_fail_when_directory_reaches_max_depth() -> Result<(), HashTableError> {
+        let mut table = HashTable::with_hasher(1, BuildIdentityHasher::default());
+        table.set_max_depth(3);
+
+        table.put(0, 0)?;
+        assert_eq!(table.put(8, 8), Err(HashTableError::DirectoryFull { max_depth: 3 }));
+        assert_eq!(table.depth, 3);
+        assert_eq!(table.get(&0), vec![0]);
+        assert!(table.get(&8).is_empty());
+
+        table.put(1, 1)?;
+        assert_eq!(table.get(&1), vec![1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_count_and_iterate_tuples() -> Result<(), HashTableError> {
+        let mut table = HashTable::new(2);
+        assert!(table.is_empty());
+
+        for i in 0..50 {
+            table.put(i % 10, i)?;
+        }
+        assert_eq!(table.len(), 50);
+
+        let mut tuples: Vec<(u32, u32)> = table.iter().map(|(key, value)| (*key, *value)).collect();
+        tuples.sort_unstable();
+        let mut expected: Vec<(u32, u32)> = (0..50).map(|i| (i % 10, i)).collect();
+        expected.sort_unstable();
+        assert_eq!(tuples, expected);
+        let values: Vec<u32> = table.iter().filter(|(key, _)| **key == 3).map(|(_, value)| *value).collect();
+        assert_eq!(values, vec![3, 13, 23, 33, 43]);
+
+        let mut keys: Vec<u32> = table.keys().copied().collect();
+        keys.sort_unstable();
+        assert_eq!(keys, (0..10).collect::<Vec<_>>());
+
+        table.remove(&3);
+        table.remove_value(&4, &14);
+        table.retain(|key, _| *key != 5);
+        assert_eq!(table.len(), 39);
+        assert_eq!(table.iter().count(), 39);
+        assert_eq!(table.verify(), Ok(()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_list_keys_of_long_overflow_chain() -> Result<(), HashTableError> {
+        let mut table = HashTable::new(4);
+
+        for i in 0..2000 {
+            table.put(7, i)?;
+        }
+        table.put(8, 0)?;
+        table.put(9, 0)?;
+
+        let mut keys: Vec<u32> = table.keys().copied().collect();
+        keys.sort_unstable();
+        assert_eq!(keys, vec![7, 8, 9]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_put_many_values_under_one_key() -> Result<(), HashTableError> {
+        let mut table = HashTable::new(1);
+
+        for i in 0..200_000 {
+            table.put(7, i)?;
+        }
+        table.put(8, 0)?;
+
+        assert_eq!(table.len(), 200_001);
+        assert_eq!(table.get(&7).len(), 200_000);
+        assert_eq!(table.verify(), Ok(()));
+
+        // Removing the key takes the tuples of every overflow bucket at once.
+        assert_eq!(table.remove(&7).len(), 200_000);
+        assert_eq!(table.get(&8), vec![0]);
+        assert_eq!(table.verify(), Ok(()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_drain_tuples() -> Result<(), HashTableError> {
+        let mut table = HashTable::new(4);
+
+        for i in 0..100 {
+            table.put(i, i * 2)?;
+        }
+        let mut drained: Vec<(u32, u32)> = table.drain().collect();
+        drained.sort_unstable();
+
+        assert_eq!(drained, (0..100).map(|i| (i, i * 2)).collect::<Vec<_>>());
+        assert!(table.is_empty());
+        assert_eq!(table.depth, 1);
+        assert!(table.get(&10).is_empty());
+
+        table.put(10, 1)?;
+        assert_eq!(table.get(&10), vec![1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_collect_and_extend() {
+        let mut table: HashTable<u32, u32> = (0..100).map(|i| (i % 7, i)).collect();
+        table.extend([(100, 1), (3, 2)]);
+
+        assert_eq!(table.len(), 102);
+        assert_eq!(table.get(&100), vec![1]);
+        assert_eq!(table.get(&3).last(), Some(&2));
+    }
+
+    #[test]
+    fn should_stop_extending_when_directory_is_full() {
+        let mut table = HashTable::with_hasher(1, BuildIdentityHasher::default());
+        table.set_max_depth(3);
+
+        assert_eq!(
+            table.try_extend([(0, 0), (1, 1), (8, 8), (2, 2)]),
+            Err(HashTableError::DirectoryFull { max_depth: 3 })
+        );
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.get(&1), vec![1]);
+        assert!(table.get(&8).is_empty() && table.get(&2).is_empty());
+    }
+
+    #[test]
+    fn should_update_through_entry() -> Result<(), HashTableError> {
+        let mut table = HashTable::new(2);
+
+        for word in ["a", "b", "a", "c", "a", "b"] {
+            *table.entry(word).or_insert(0)? += 1;
+        }
+        assert_eq!(table.get(&"a"), vec![3]);
+        assert_eq!(table.get(&"b"), vec![2]);
+        assert_eq!(table.len(), 3);
+
+        table.entry("c").and_modify(|count| *count *= 10).or_insert(0)?;
+        table.entry("d").and_modify(|count| *count *= 10).or_insert_with(|| 7)?;
+        assert_eq!(table.get(&"c"), vec![10]);
+        assert_eq!(table.get(&"d"), vec![7]);
+        assert_eq!(*table.entry("d").key(), "d");
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_replace_value_in_unique_mode() -> Result<(), HashTableError> {
+        let mut table = HashTable::new_unique(2);
+
+        assert_eq!(table.insert(10, 11)?, None);
+        assert_eq!(table.insert(10, 12)?, Some(11));
+        assert_eq!(table.get(&10), Some(&12));
+        assert_eq!(table.get(&20), None);
+        assert_eq!(table.len(), 1);
+
+        for i in 0..1000 {
+            table.insert(i, i)?;
+            table.insert(i, i + 1)?;
+        }
+        assert_eq!(table.len(), 1000);
+        for i in 0..1000 {
+            assert_eq!(table.get(&i), Some(&(i + 1)));
+        }
+
+        assert_eq!(table.remove(&10), Some(11));
+        assert_eq!(table.remove(&10), None);
+        assert_eq!(table.len(), 999);
+        assert_eq!(table.verify(), Ok(()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_keep_last_value_when_collecting_unique() {
+        let table: HashTable<u32, u32, BuildIdentityHasher, Unique> = (0..100).map(|i| (i % 7, i)).collect();
+
+        assert_eq!(table.len(), 7);
+        assert_eq!(table.get(&3), Some(&94));
+    }
+
+    #[test]
+    fn should_detect_broken_invariants() -> Result<(), HashTableError> {
+        let mut table = HashTable::with_hasher(2, BuildIdentityHasher::default());
+        for i in 0..16 {
+            table.put(i, i)?;
+        }
+        assert_eq!(table.verify(), Ok(()));
+
+        let mut misplaced = table.clone();
+        let bucket = misplaced.directories[0];
+        misplaced.buckets[bucket].tuples[0].key = 1;
+        assert!(misplaced.verify().unwrap_err().contains("hash 0x1"));
+
+        let mut miscounted = table.clone();
+        miscounted.len -= 1;
+        assert!(miscounted.verify().is_err());
+
+        let mut repointed = table.clone();
+        repointed.directories[1] = repointed.directories[0];
+        assert!(repointed.verify().is_err());
+
+        let mut deepened = table;
+        deepened.buckets[0].depth = deepened.depth + 1;
+        assert!(deepened.verify().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_describe_shape() -> Result<(), HashTableError> {
+        let mut table = HashTable::with_hasher(2, BuildIdentityHasher::default());
+        for i in 0..8 {
+            table.put(i, i)?;
+        }
+        for value in 0..3 {
+            table.put(8, value)?;
+        }
+
+        let report = table.describe();
+        assert_eq!(report.global_depth, 4);
+        assert_eq!(report.directory_size, 16);
+        assert_eq!(report.buckets, 6);
+        assert_eq!(report.overflow_buckets, 1);
+        assert_eq!(report.local_depths.into_iter().collect::<Vec<_>>(), vec![(2, 3), (3, 1), (4, 2)]);
+        assert!((report.load_factor - 11.0 / 14.0).abs() < 1e-9);
+        assert!(table.describe().to_string().contains("directory size: 16"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,374 @@
+use std::hash::{BuildHasher, Hash};
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::mem::size_of;
+
+use super::{hash_key, Bucket, HashTable, KeyMode, Tuple};
+
+const MAGIC: &[u8; 4] = b"EHTS";
+const VERSION: u16 = 1;
+
+/// A key or value that can be written to a `HashTable` snapshot.
+pub trait Codec: Sized {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+
+    fn decode<R: Read>(reader: &mut R) -> io::Result<Self>;
+}
+
+macro_rules! impl_codec {
+    ($($t:ty),*) => {
+        $(
+            impl Codec for $t {
+                fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+                    writer.write_all(&self.to_le_bytes())
+                }
+
+                fn decode<R: Read>(reader: &mut R) -> io::Result<Self> {
+                    let mut buf = [0; size_of::<$t>()];
+                    reader.read_exact(&mut buf)?;
+                    Ok(<$t>::from_le_bytes(buf))
+                }
+            }
+        )*
+    };
+}
+
+impl_codec!(u8, u16, u32, u64, i32, i64);
+
+impl Codec for String {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        (self.len() as u32).encode(writer)?;
+        writer.write_all(self.as_bytes())
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> io::Result<Self> {
+        // Read through `take`, so a corrupted length cannot allocate more than
+        // the reader holds.
+        let len = u32::decode(reader)? as u64;
+        let mut buf = Vec::new();
+        if reader.take(len).read_to_end(&mut buf)? as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl<A: Codec, B: Codec> Codec for (A, B) {
+    fn encode<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.0.encode(writer)?;
+        self.1.encode(writer)
+    }
+
+    fn decode<R: Read>(reader: &mut R) -> io::Result<Self> {
+        Ok((A::decode(reader)?, B::decode(reader)?))
+    }
+}
+
+/// A snapshot stores the magic bytes, the format version, the key mode, the
+/// global and maximum depths, the bucket size limit and the bucket count. The
+/// directory follows as one bucket number per slot, so buckets shared by
+/// several slots are stored once, and then every bucket as its local depth,
+/// its tuple count and its tuples. A CRC-32 of everything before it ends the
+/// snapshot.
+///
+/// Loading restores the buckets as they were, without re-inserting the tuples.
+/// The placement of the tuples depends on the hasher, so a table must be loaded
+/// with a hasher that hashes like the one it was saved with, e.g.
+/// `BuildHasherDefault<DefaultHasher>` rather than `RandomState`.
+impl<K: Hash + Eq + Codec, V: Codec, S: BuildHasher, M: KeyMode> HashTable<K, V, S, M> {
+    pub(crate) fn save<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = CrcWriter { inner: writer, crc: !0 };
+        writer.write_all(MAGIC)?;
+        VERSION.encode(&mut writer)?;
+        (M::UNIQUE as u8).encode(&mut writer)?;
+        self.depth.encode(&mut writer)?;
+        self.max_depth.encode(&mut writer)?;
+        (self.bucket_size_limit as u64).encode(&mut writer)?;
+        (self.buckets.len() as u32).encode(&mut writer)?;
+        for bucket in &self.directories {
+            (*bucket as u32).encode(&mut writer)?;
+        }
+        for bucket in &self.buckets {
+            (bucket.depth as u8).encode(&mut writer)?;
+            (bucket.tuples().count() as u64).encode(&mut writer)?;
+            for tuple in bucket.tuples() {
+                tuple.key.encode(&mut writer)?;
+                tuple.value.encode(&mut writer)?;
+            }
+        }
+
+        let checksum = !writer.crc;
+        checksum.encode(&mut writer.inner)?;
+        writer.inner.flush()
+    }
+
+    /// Reads a snapshot written by `save`. Fails with `InvalidData` if the
+    /// snapshot is corrupted, of another version or key mode, or does not hold
+    /// the invariants checked by `verify` under the given hasher.
+    pub(crate) fn load<R: Read>(reader: R, hash_builder: S) -> io::Result<Self> {
+        let mut reader = CrcReader { inner: reader, crc: !0 };
+        let table = Self::read_table(&mut reader, hash_builder).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => invalid_data("the snapshot is truncated".to_string()),
+            _ => e,
+        })?;
+        table.verify().map_err(invalid_data)?;
+        Ok(table)
+    }
+
+    /// Decodes the snapshot and checks its checksum, which is only known once
+    /// all of it has been read.
+    fn read_table<R: Read>(reader: &mut CrcReader<R>, hash_builder: S) -> io::Result<Self> {
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a hash table snapshot".to_string()));
+        }
+        let version = u16::decode(reader)?;
+        if version != VERSION {
+            return Err(invalid_data(format!("unsupported snapshot version {}", version)));
+        }
+        if (u8::decode(reader)? == 1) != M::UNIQUE {
+            return Err(invalid_data("the snapshot is of a table in the other key mode".to_string()));
+        }
+        let depth = u32::decode(reader)?;
+        let max_depth = u32::decode(reader)?;
+        let bucket_size_limit = u64::decode(reader)? as usize;
+        let bucket_count = u32::decode(reader)?;
+        if depth > max_depth || max_depth > 32 || bucket_size_limit == 0 {
+            return Err(invalid_data(format!("invalid limits {}, {} and {}", depth, max_depth, bucket_size_limit)));
+        }
+
+        let directories =
+            (0..1u64 << depth).map(|_| u32::decode(reader).map(|b| b as usize)).collect::<io::Result<_>>()?;
+        let mut buckets = Vec::new();
+        let mut len = 0;
+        for _ in 0..bucket_count {
+            let mut bucket = Bucket::new();
+            bucket.depth = u8::decode(reader)? as u32;
+            let size = u64::decode(reader)?;
+            let tuples = (0..size)
+                .map(|_| {
+                    let key = K::decode(reader)?;
+                    Ok(Tuple { hash: hash_key(&hash_builder, &key), key, value: V::decode(reader)? })
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            len += tuples.len();
+            bucket.set_tuples(tuples, bucket_size_limit);
+            buckets.push(bucket);
+        }
+
+        let checksum = !reader.crc;
+        if u32::decode(&mut reader.inner)? != checksum {
+            return Err(invalid_data("the snapshot checksum does not match".to_string()));
+        }
+        if reader.inner.read(&mut [0])? != 0 {
+            return Err(invalid_data("the snapshot has trailing bytes".to_string()));
+        }
+
+        Ok(HashTable {
+            depth,
+            max_depth,
+            directories,
+            buckets,
+            len,
+            bucket_size_limit,
+            hash_builder,
+            mode: PhantomData,
+        })
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Feeds the data to a CRC-32 of IEEE 802.3, computed bit by bit. The CRC is
+/// started at and finished by inverting all of its bits.
+fn update_crc32(mut crc: u32, data: &[u8]) -> u32 {
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+/// Computes the CRC-32 of everything written through it.
+struct CrcWriter<W> {
+    inner: W,
+    crc: u32,
+}
+
+impl<W: Write> Write for CrcWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.crc = update_crc32(self.crc, &buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Computes the CRC-32 of everything read through it.
+struct CrcReader<R> {
+    inner: R,
+    crc: u32,
+}
+
+impl<R: Read> Read for CrcReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.crc = update_crc32(self.crc, &buf[..read]);
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::BuildHasherDefault;
+    use std::io;
+
+    use crate::hash_table::{BuildIdentityHasher, HashTable, Unique};
+
+    use super::{update_crc32, Codec};
+
+    type FixedState = BuildHasherDefault<DefaultHasher>;
+
+    fn crc32(data: &[u8]) -> u32 {
+        !update_crc32(!0, data)
+    }
+
+    fn table(size: u32) -> HashTable<u32, u64, FixedState> {
+        let mut table = HashTable::with_hasher(8, FixedState::default());
+        for i in 0..size {
+            table.put(i, i as u64).unwrap();
+            if i % 10 == 0 {
+                table.put(i, 0).unwrap();
+            }
+        }
+        table
+    }
+
+    fn snapshot(table: &HashTable<u32, u64, FixedState>) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        table.save(&mut data)?;
+        Ok(data)
+    }
+
+    #[test]
+    fn should_compute_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn should_round_trip_table() -> io::Result<()> {
+        let table = table(5000);
+        let data = snapshot(&table)?;
+
+        let loaded: HashTable<u32, u64, FixedState> = HashTable::load(data.as_slice(), FixedState::default())?;
+        assert_eq!(loaded.depth, table.depth);
+        assert_eq!(loaded.directories, table.directories);
+        assert_eq!(loaded.len(), table.len());
+        assert_eq!(loaded.verify(), Ok(()));
+        for i in 0..5000 {
+            assert_eq!(loaded.get(&i), table.get(&i));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_round_trip_overflow_and_unique_tables() -> io::Result<()> {
+        let mut overflowing = HashTable::with_hasher(2, BuildIdentityHasher::default());
+        for value in 0..20u64 {
+            overflowing.put(7u32, value).unwrap();
+        }
+        let mut data = Vec::new();
+        overflowing.save(&mut data)?;
+        let loaded: HashTable<u32, u64, _> = HashTable::load(data.as_slice(), BuildIdentityHasher::default())?;
+        assert_eq!(loaded.get(&7), (0..20).collect::<Vec<_>>());
+        assert_eq!(loaded.describe(), overflowing.describe());
+
+        let mut unique = HashTable::with_hasher_unique(4, FixedState::default());
+        for name in ["alpha", "beta", "gamma", "delta", "epsilon"] {
+            unique.insert(name.to_string(), (name.len() as u32, name.as_bytes()[0] as u64)).unwrap();
+        }
+        let mut data = Vec::new();
+        unique.save(&mut data)?;
+        let loaded: HashTable<String, (u32, u64), _, Unique> = HashTable::load(data.as_slice(), FixedState::default())?;
+        assert_eq!(loaded.get(&"gamma".to_string()), Some(&(5, b'g' as u64)));
+        assert_eq!(loaded.len(), 5);
+
+        Ok(())
+    }
+
+    /// Records the largest write, to tell whether the snapshot is streamed.
+    struct ChunkWriter {
+        data: Vec<u8>,
+        largest: usize,
+    }
+
+    impl io::Write for ChunkWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.largest = self.largest.max(buf.len());
+            self.data.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_stream_snapshot() -> io::Result<()> {
+        let table = table(5000);
+        let mut writer = ChunkWriter { data: Vec::new(), largest: 0 };
+        table.save(&mut writer)?;
+        assert_eq!(writer.data, snapshot(&table)?);
+        assert!(writer.largest <= 8);
+
+        let loaded: HashTable<u32, u64, FixedState> =
+            HashTable::load(io::BufReader::with_capacity(16, writer.data.as_slice()), FixedState::default())?;
+        assert_eq!(loaded.len(), table.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_reject_corrupted_snapshots() -> io::Result<()> {
+        let data = snapshot(&table(100))?;
+        let load = |data: &[u8]| HashTable::<u32, u64, FixedState>::load(data, FixedState::default()).err().unwrap();
+
+        let mut flipped = data.clone();
+        flipped[20] ^= 1;
+        assert_eq!(load(&flipped).kind(), io::ErrorKind::InvalidData);
+        assert_eq!(load(&data[..data.len() - 1]).kind(), io::ErrorKind::InvalidData);
+        assert_eq!(load(b"EHTS").kind(), io::ErrorKind::InvalidData);
+        assert_eq!(load(&[data.as_slice(), &[0]].concat()).kind(), io::ErrorKind::InvalidData);
+
+        let mut newer = data[..data.len() - 4].to_vec();
+        newer[4..6].copy_from_slice(&2u16.to_le_bytes());
+        crc32(&newer).encode(&mut newer)?;
+        assert!(load(&newer).to_string().contains("version 2"));
+
+        let unique = HashTable::<u32, u64, FixedState, Unique>::load(data.as_slice(), FixedState::default());
+        assert_eq!(unique.err().unwrap().kind(), io::ErrorKind::InvalidData);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_reject_snapshot_of_other_hasher() -> io::Result<()> {
+        let data = snapshot(&table(100))?;
+
+        let error = HashTable::<u32, u64, _>::load(data.as_slice(), BuildIdentityHasher::default()).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        Ok(())
+    }
+}
@@ -2,4 +2,10 @@ extern crate core;
 mod buffer;
 mod types;
 mod hash_table;
+mod storage;
 mod aa_tree;
+mod b_plus_tree;
+mod disk_hash_table;
+mod concurrent_hash_table;
+mod linear_hash_table;
+mod execution;
@@ -0,0 +1,226 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+
+use crate::hash_table::{hash_key, mask, HashTableError, DEFAULT_MAX_DEPTH};
+
+/// The load factor that triggers a split unless set otherwise.
+const DEFAULT_MAX_LOAD_FACTOR: f64 = 0.8;
+
+/// A linear hashing table with the same `get`/`put` multimap interface as
+/// `hash_table::HashTable`.
+///
+/// Instead of doubling a directory, the table grows one bucket at a time. When
+/// the load factor passes the trigger, the bucket at the split pointer is
+/// split into itself and a new bucket at the end, and the pointer moves on.
+/// Buckets before the pointer have split already and are addressed by one more
+/// hash bit. When every bucket of the level has split, the level grows and the
+/// pointer starts over. A bucket may hold more than `bucket_size_limit` tuples
+/// until its turn to split comes; the limit only sets the capacity the load
+/// factor is computed against.
+#[derive(Clone, Debug)]
+pub struct LinearHashTable<K, V, S = RandomState> {
+    level: u32,
+    /// The next bucket to split.
+    next: usize,
+    buckets: Vec<Vec<(K, V)>>,
+    len: usize,
+    bucket_size_limit: usize,
+    max_load_factor: f64,
+    max_depth: u32,
+    hash_builder: S,
+}
+
+impl<K: Hash + Eq, V> LinearHashTable<K, V, RandomState> {
+    pub fn new(bucket_size_limit: usize) -> Self {
+        LinearHashTable::with_hasher(bucket_size_limit, RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> LinearHashTable<K, V, S> {
+    pub fn with_hasher(bucket_size_limit: usize, hash_builder: S) -> Self {
+        assert!(bucket_size_limit > 0, "a bucket must hold at least one tuple");
+        LinearHashTable {
+            level: 1,
+            next: 0,
+            buckets: vec![Vec::new(), Vec::new()],
+            len: 0,
+            bucket_size_limit,
+            max_load_factor: DEFAULT_MAX_LOAD_FACTOR,
+            max_depth: DEFAULT_MAX_DEPTH,
+            hash_builder,
+        }
+    }
+
+    /// Sets the load factor above which a bucket splits.
+    pub fn set_max_load_factor(&mut self, max_load_factor: f64) {
+        assert!(max_load_factor > 0.0, "invalid maximum load factor {}", max_load_factor);
+        self.max_load_factor = max_load_factor;
+    }
+
+    /// Sets the largest level, so the table has at most `2^max_depth` buckets.
+    pub fn set_max_depth(&mut self, max_depth: u32) {
+        assert!((self.level..=32).contains(&max_depth), "invalid maximum depth {}", max_depth);
+        self.max_depth = max_depth;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The tuples divided by the capacity of all buckets.
+    pub fn load_factor(&self) -> f64 {
+        self.len as f64 / (self.buckets.len() * self.bucket_size_limit) as f64
+    }
+
+    pub fn get(&self, key: &K) -> Vec<V>
+    where
+        V: Clone,
+    {
+        let bucket = &self.buckets[self.bucket_index(self.hash(key))];
+        bucket.iter().filter(|(k, _)| k == key).map(|(_, value)| value.clone()).collect()
+    }
+
+    /// Appends the value to the values of the key. Fails if the tuple would
+    /// push the load factor past the trigger and the table already has
+    /// `2^max_depth` buckets.
+    pub fn put(&mut self, key: K, value: V) -> Result<(), HashTableError> {
+        while (self.len + 1) as f64 > self.max_load_factor * (self.buckets.len() * self.bucket_size_limit) as f64 {
+            if self.buckets.len() >= 1 << self.max_depth {
+                return Err(HashTableError::DirectoryFull { max_depth: self.max_depth });
+            }
+            self.split_next();
+        }
+
+        let idx = self.bucket_index(self.hash(&key));
+        self.buckets[idx].push((key, value));
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Moves the tuples of the bucket at the split pointer that differ from it
+    /// in the next hash bit to a new bucket at the end.
+    fn split_next(&mut self) {
+        let depth = self.level + 1;
+        let (keep, moved) = std::mem::take(&mut self.buckets[self.next])
+            .into_iter()
+            .partition(|(key, _)| mask(self.hash(key), depth) as usize == self.next);
+        self.buckets[self.next] = keep;
+        self.buckets.push(moved);
+
+        self.next += 1;
+        if self.next == 1 << self.level {
+            self.level += 1;
+            self.next = 0;
+        }
+    }
+
+    fn bucket_index(&self, hash: u32) -> usize {
+        let idx = mask(hash, self.level) as usize;
+        if idx < self.next { mask(hash, self.level + 1) as usize } else { idx }
+    }
+
+    fn hash(&self, key: &K) -> u32 {
+        hash_key(&self.hash_builder, key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::hash_table::{BuildIdentityHasher, HashTableError};
+
+    use super::LinearHashTable;
+
+    #[test]
+    fn should_not_find_non_existent_value() {
+        let table: LinearHashTable<u32, u32> = LinearHashTable::new(3);
+
+        assert!(table.get(&10).is_empty());
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn should_put_get_multiple_values() -> Result<(), HashTableError> {
+        let mut table = LinearHashTable::new(3);
+
+        table.put(10, 11)?;
+        table.put(10, 12)?;
+        table.put(20, 21)?;
+        assert_eq!(table.get(&10), vec![11, 12]);
+        assert_eq!(table.get(&20), vec![21]);
+        assert_eq!(table.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_put_get_many() -> Result<(), HashTableError> {
+        let mut table = LinearHashTable::new(16);
+
+        for i in 1..10000 {
+            table.put(i, i)?;
+            table.put(i, 2 * i)?;
+            assert_eq!(table.get(&i), vec![i, 2 * i]);
+        }
+
+        for i in 1..10000 {
+            assert_eq!(table.get(&i), vec![i, 2 * i]);
+        }
+        assert!(table.load_factor() <= 0.8);
+        Ok(())
+    }
+
+    #[test]
+    fn should_grow_one_bucket_at_a_time() -> Result<(), HashTableError> {
+        let mut table = LinearHashTable::with_hasher(4, BuildIdentityHasher::default());
+        table.set_max_load_factor(1.0);
+
+        for i in 0..1000 {
+            let buckets = table.buckets.len();
+            table.put(i, i)?;
+
+            assert!(table.buckets.len() - buckets <= 1);
+            assert_eq!(table.buckets.len(), (1 << table.level) + table.next);
+            assert_eq!(table.buckets.len(), (i as usize / 4 + 1).max(2));
+        }
+        for i in 0..1000 {
+            assert_eq!(table.get(&i), vec![i]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn should_split_sooner_with_lower_load_factor() -> Result<(), HashTableError> {
+        let mut dense = LinearHashTable::new(8);
+        let mut sparse = LinearHashTable::new(8);
+        sparse.set_max_load_factor(0.25);
+
+        for i in 0..1000 {
+            dense.put(i, i)?;
+            sparse.put(i, i)?;
+        }
+        assert!(sparse.buckets.len() > 3 * dense.buckets.len());
+        assert!(sparse.load_factor() <= 0.25);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_fail_when_max_depth_reached() -> Result<(), HashTableError> {
+        let mut table = LinearHashTable::new(1);
+        table.set_max_load_factor(1.0);
+        table.set_max_depth(2);
+
+        for i in 0..4 {
+            table.put(i, i)?;
+        }
+        assert_eq!(table.put(4, 4), Err(HashTableError::DirectoryFull { max_depth: 2 }));
+        assert_eq!(table.len(), 4);
+        assert!(table.get(&4).is_empty());
+
+        Ok(())
+    }
+}
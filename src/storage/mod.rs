@@ -0,0 +1,3 @@
+mod overflow;
+mod slotted_page;
+pub(crate) mod table_heap;
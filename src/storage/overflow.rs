@@ -0,0 +1,224 @@
+use std::io::{self, Read};
+
+use crate::buffer::buffer_pool::{no_free_frame, page_not_deallocated};
+use crate::buffer::buffer_pool_instance::BufferPoolInstance;
+use crate::buffer::constants::PAGE_SIZE;
+use crate::buffer::page_view::{PageHeader, PageType, PageView};
+use crate::buffer::types::PageId;
+
+const NEXT_PAGE_ID_OFFSET: usize = PageHeader::SIZE;
+const DATA_LEN_OFFSET: usize = NEXT_PAGE_ID_OFFSET + 8;
+const DATA_OFFSET: usize = DATA_LEN_OFFSET + 2;
+const NO_NEXT_PAGE: u64 = u64::MAX;
+
+/// The number of value bytes stored in one overflow page.
+pub const OVERFLOW_PAGE_CAPACITY: usize = PAGE_SIZE - DATA_OFFSET;
+
+/// Stores the value in a chain of overflow pages and returns the id of the
+/// first one. The chain is written from its end, so that every page already
+/// knows its successor and only one frame is pinned at a time.
+pub fn write_chain(pool: &mut BufferPoolInstance, value: &[u8]) -> io::Result<PageId> {
+    let mut next_page_id = None;
+    let mut written = vec![];
+
+    let chunks: Vec<&[u8]> =
+        if value.is_empty() { vec![value] } else { value.chunks(OVERFLOW_PAGE_CAPACITY).collect() };
+    for chunk in chunks.into_iter().rev() {
+        match write_page(pool, chunk, next_page_id) {
+            Ok(page_id) => {
+                written.push(page_id);
+                next_page_id = Some(page_id);
+            }
+            Err(e) => {
+                written.into_iter().rev().try_for_each(|page_id| free_page(pool, page_id).map(|_next| ()))?;
+                return Err(e);
+            }
+        }
+    }
+    Ok(next_page_id.unwrap())
+}
+
+pub fn read_chain(pool: &mut BufferPoolInstance, first_page_id: PageId) -> io::Result<Vec<u8>> {
+    let mut value = vec![];
+    OverflowReader::new(pool.clone(), first_page_id).read_to_end(&mut value)?;
+    Ok(value)
+}
+
+/// Zeroes every page of the chain and deallocates it, so new pages reuse it.
+pub fn free_chain(pool: &mut BufferPoolInstance, first_page_id: PageId) -> io::Result<()> {
+    let mut page_id = Some(first_page_id);
+    while let Some(current) = page_id {
+        page_id = free_page(pool, current)?;
+    }
+    Ok(())
+}
+
+fn write_page(pool: &mut BufferPoolInstance, chunk: &[u8], next_page_id: Option<PageId>) -> io::Result<PageId> {
+    let mut page = pool.new_page()?.ok_or_else(no_free_frame)?;
+    let page_id = page.get_page_id().unwrap();
+
+    let written = page.write_data(|data| {
+        let mut view = PageView::new(data);
+        view.write_header(&PageHeader::new(PageType::Overflow, (DATA_OFFSET + chunk.len()) as u16))?;
+        view.write_u64_at(NEXT_PAGE_ID_OFFSET, next_page_id.map_or(NO_NEXT_PAGE, |page_id| page_id as u64))?;
+        view.write_u16_at(DATA_LEN_OFFSET, chunk.len() as u16)?;
+        view.write_bytes_at(DATA_OFFSET, chunk)
+    });
+    pool.unpin_page(page_id, true)?;
+    written.map(|_| page_id)
+}
+
+/// Frees one page of a chain and returns the next one.
+fn free_page(pool: &mut BufferPoolInstance, page_id: PageId) -> io::Result<Option<PageId>> {
+    let mut page = pool.fetch_page(page_id)?.ok_or_else(no_free_frame)?;
+    let next_page_id = page.read_data(|data| read_page(data, &mut vec![]));
+    if next_page_id.is_ok() {
+        page.write_data(|data| data.fill(0));
+    }
+    pool.unpin_page(page_id, true)?;
+    if !pool.deallocate_page(page_id) {
+        return Err(page_not_deallocated(page_id));
+    }
+    next_page_id
+}
+
+/// Appends the value bytes of the page to `chunk` and returns the next page.
+pub(super) fn read_page(data: &[u8], chunk: &mut Vec<u8>) -> io::Result<Option<PageId>> {
+    let view = PageView::new(data);
+    if view.read_header()?.page_type != PageType::Overflow {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not an overflow page"));
+    }
+    let len = view.read_u16_at(DATA_LEN_OFFSET)? as usize;
+    chunk.extend_from_slice(view.read_bytes_at(DATA_OFFSET, len)?);
+
+    let next_page_id = view.read_u64_at(NEXT_PAGE_ID_OFFSET)?;
+    Ok(Some(next_page_id as PageId).filter(|_| next_page_id != NO_NEXT_PAGE))
+}
+
+/// Streams a value out of its overflow chain, keeping only one page of it in
+/// memory.
+pub struct OverflowReader {
+    pool: BufferPoolInstance,
+    next_page_id: Option<PageId>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl OverflowReader {
+    pub fn new(pool: BufferPoolInstance, first_page_id: PageId) -> Self {
+        OverflowReader { pool, next_page_id: Some(first_page_id), chunk: Vec::new(), position: 0 }
+    }
+}
+
+impl Read for OverflowReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.chunk.len() {
+            let page_id = match self.next_page_id {
+                Some(page_id) => page_id,
+                None => return Ok(0),
+            };
+            self.chunk.clear();
+            self.position = 0;
+
+            let page = self.pool.fetch_page(page_id)?.ok_or_else(no_free_frame)?;
+            let next_page_id = page.read_data(|data| read_page(data, &mut self.chunk));
+            self.pool.unpin_page(page_id, false)?;
+            self.next_page_id = next_page_id?;
+        }
+
+        let len = buf.len().min(self.chunk.len() - self.position);
+        buf[..len].copy_from_slice(&self.chunk[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::{self, Read};
+
+    use crate::buffer::buffer_pool_instance::BufferPoolInstance;
+    use crate::buffer::disk_manager::DiskManager;
+    use crate::buffer::test_utils::TempFile;
+    use crate::types::{thread_rng, RngCore};
+
+    use super::{free_chain, read_chain, write_chain, OverflowReader, OVERFLOW_PAGE_CAPACITY};
+
+    fn random_value(len: usize) -> Vec<u8> {
+        let mut value = vec![0; len];
+        thread_rng().fill_bytes(&mut value);
+        value
+    }
+
+    #[test]
+    fn should_write_read_chain() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let mut pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 2);
+
+        for len in [0, 1, OVERFLOW_PAGE_CAPACITY, OVERFLOW_PAGE_CAPACITY + 1, 10 * OVERFLOW_PAGE_CAPACITY + 17] {
+            let value = random_value(len);
+            let first_page_id = write_chain(&mut pool, &value)?;
+            assert_eq!(read_chain(&mut pool, first_page_id)?, value);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn should_stream_chain() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let mut pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 1);
+        let value = random_value(5 * OVERFLOW_PAGE_CAPACITY + 3);
+        let first_page_id = write_chain(&mut pool, &value)?;
+
+        let mut reader = OverflowReader::new(pool.clone(), first_page_id);
+        let mut streamed = vec![];
+        let mut buf = [0; 1000];
+        loop {
+            let len = reader.read(&mut buf)?;
+            if len == 0 {
+                break;
+            }
+            streamed.extend_from_slice(&buf[..len]);
+        }
+        assert_eq!(streamed, value);
+        Ok(())
+    }
+
+    #[test]
+    fn should_free_chain() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let mut pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 2);
+        let first_page_id = write_chain(&mut pool, &random_value(3 * OVERFLOW_PAGE_CAPACITY))?;
+
+        free_chain(&mut pool, first_page_id)?;
+
+        for page_id in first_page_id - 2..=first_page_id {
+            let freed = pool.fetch_page(page_id).err().map(|err| err.kind());
+            assert_eq!(freed, Some(io::ErrorKind::InvalidInput));
+        }
+        assert_eq!(read_chain(&mut pool, first_page_id).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+        // The next chain takes the freed pages, so the file does not grow.
+        let len = fs::metadata(file.path())?.len();
+        let value = random_value(3 * OVERFLOW_PAGE_CAPACITY);
+        assert_eq!(write_chain(&mut pool, &value)?, first_page_id);
+        assert_eq!(read_chain(&mut pool, first_page_id)?, value);
+        free_chain(&mut pool, first_page_id)?;
+        assert_eq!(fs::metadata(file.path())?.len(), len);
+        Ok(())
+    }
+
+    #[test]
+    fn should_report_pinned_page_on_free() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let mut pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 2);
+        let first_page_id = write_chain(&mut pool, &random_value(OVERFLOW_PAGE_CAPACITY))?;
+
+        assert!(pool.fetch_page(first_page_id)?.is_some());
+        let error = free_chain(&mut pool, first_page_id).unwrap_err();
+        assert!(error.to_string().contains(&format!("page {} is pinned", first_page_id)));
+        pool.unpin_page(first_page_id, false)?;
+        Ok(())
+    }
+}
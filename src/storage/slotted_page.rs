@@ -0,0 +1,489 @@
+use std::io;
+
+use crate::buffer::constants::PAGE_SIZE;
+use crate::buffer::page_view::{PageHeader, PageType, PageView};
+use crate::buffer::types::PageId;
+
+pub type SlotId = u16;
+
+/// The size of the largest record that fits into an empty page.
+pub const MAX_RECORD_SIZE: usize = PAGE_SIZE - SlottedPage::<&[u8]>::SLOTS_OFFSET - Slot::SIZE;
+
+/// A page of variable-length records. The slot directory grows from the front
+/// of the page, right after the header, and the records grow from the back.
+/// The free space pointer of the header marks the start of the record data.
+///
+/// A slot stores the offset and the length of its record. Deleted records
+/// leave an empty slot behind, so that the ids of the other slots stay stable;
+/// the empty slot is reused by the next insert and the record bytes are
+/// reclaimed by compaction. A record can also be marked as deleted first: it is
+/// hidden from readers but keeps its space until the delete is applied.
+///
+/// The pages can be chained into a list through the next page id stored after
+/// the header.
+pub struct SlottedPage<B>(PageView<B>);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Slot {
+    offset: u16,
+    len: u16,
+}
+
+impl Slot {
+    const SIZE: usize = 4;
+    const EMPTY: Slot = Slot { offset: 0, len: 0 };
+    const DELETE_MASK: u16 = 1 << 15;
+
+    fn is_empty(&self) -> bool {
+        self.offset == 0
+    }
+
+    fn is_marked_deleted(&self) -> bool {
+        self.len & Slot::DELETE_MASK != 0
+    }
+
+    fn record_len(&self) -> usize {
+        (self.len & !Slot::DELETE_MASK) as usize
+    }
+}
+
+impl<B: AsRef<[u8]>> SlottedPage<B> {
+    const NEXT_PAGE_ID_OFFSET: usize = PageHeader::SIZE;
+    const SLOT_COUNT_OFFSET: usize = SlottedPage::<B>::NEXT_PAGE_ID_OFFSET + 8;
+    const SLOTS_OFFSET: usize = SlottedPage::<B>::SLOT_COUNT_OFFSET + 2;
+    const NO_NEXT_PAGE: u64 = u64::MAX;
+
+    pub fn new(data: B) -> Self {
+        SlottedPage(PageView::new(data))
+    }
+
+    pub fn next_page_id(&self) -> io::Result<Option<PageId>> {
+        let next_page_id = self.0.read_u64_at(Self::NEXT_PAGE_ID_OFFSET)?;
+        Ok(Some(next_page_id as PageId).filter(|_| next_page_id != Self::NO_NEXT_PAGE))
+    }
+
+    pub fn slot_count(&self) -> io::Result<u16> {
+        self.0.read_u16_at(Self::SLOT_COUNT_OFFSET)
+    }
+
+    /// Returns the record stored in the slot, or `None` if the slot is empty,
+    /// marked as deleted or does not exist.
+    pub fn get(&self, slot_id: SlotId) -> io::Result<Option<&[u8]>> {
+        match self.live_slot(slot_id)? {
+            Some(slot) => self.0.read_bytes_at(slot.offset as usize, slot.record_len()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the record stored in the slot even if it is marked as deleted.
+    pub fn get_stored(&self, slot_id: SlotId) -> io::Result<Option<&[u8]>> {
+        match self.stored_slot(slot_id)? {
+            Some(slot) => self.0.read_bytes_at(slot.offset as usize, slot.record_len()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// The size of the largest record that can be inserted into the page,
+    /// compacting it if necessary.
+    pub fn free_space(&self) -> io::Result<usize> {
+        let slot_count = self.slot_count()?;
+        let mut used = Self::slots_end(slot_count);
+        let mut has_empty_slot = false;
+        for slot_id in 0..slot_count {
+            let slot = self.slot(slot_id)?;
+            has_empty_slot |= slot.is_empty();
+            used += slot.record_len();
+        }
+        if !has_empty_slot {
+            used += Slot::SIZE;
+        }
+        Ok(self.0.len().saturating_sub(used))
+    }
+
+    pub fn fits(&self, len: usize) -> io::Result<bool> {
+        Ok(len <= self.free_space()?)
+    }
+
+    fn live_slot(&self, slot_id: SlotId) -> io::Result<Option<Slot>> {
+        Ok(self.stored_slot(slot_id)?.filter(|slot| !slot.is_marked_deleted()))
+    }
+
+    /// Returns the slot if it holds a record, even if it is marked as deleted.
+    fn stored_slot(&self, slot_id: SlotId) -> io::Result<Option<Slot>> {
+        if slot_id < self.slot_count()? {
+            Ok(Some(self.slot(slot_id)?).filter(|slot| !slot.is_empty()))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn slot(&self, slot_id: SlotId) -> io::Result<Slot> {
+        let offset = Self::slot_offset(slot_id);
+        Ok(Slot { offset: self.0.read_u16_at(offset)?, len: self.0.read_u16_at(offset + 2)? })
+    }
+
+    fn free_space_pointer(&self) -> io::Result<usize> {
+        Ok(self.0.read_header()?.free_space_pointer as usize)
+    }
+
+    fn slot_offset(slot_id: SlotId) -> usize {
+        Self::SLOTS_OFFSET + slot_id as usize * Slot::SIZE
+    }
+
+    fn slots_end(slot_count: u16) -> usize {
+        Self::slot_offset(slot_count)
+    }
+
+    fn not_found(slot_id: SlotId) -> io::Error {
+        io::Error::new(io::ErrorKind::NotFound, format!("slot {} does not hold a record", slot_id))
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> SlottedPage<B> {
+    /// Formats the page as an empty slotted page.
+    pub fn init(&mut self) -> io::Result<()> {
+        let free_space_pointer = self.0.len() as u16;
+        self.0.write_header(&PageHeader::new(PageType::Slotted, free_space_pointer))?;
+        self.set_next_page_id(None)?;
+        self.set_slot_count(0)
+    }
+
+    pub fn set_next_page_id(&mut self, next_page_id: Option<PageId>) -> io::Result<()> {
+        let next_page_id = next_page_id.map_or(Self::NO_NEXT_PAGE, |page_id| page_id as u64);
+        self.0.write_u64_at(Self::NEXT_PAGE_ID_OFFSET, next_page_id)
+    }
+
+    /// Stores the record and returns its slot, or `None` if it does not fit.
+    pub fn insert(&mut self, record: &[u8]) -> io::Result<Option<SlotId>> {
+        if !self.fits(record.len())? {
+            return Ok(None);
+        }
+
+        let slot_count = self.slot_count()?;
+        let mut slot_id = slot_count;
+        for id in 0..slot_count {
+            if self.slot(id)?.is_empty() {
+                slot_id = id;
+                break;
+            }
+        }
+        if slot_id == slot_count {
+            self.set_slot_count(slot_count + 1)?;
+            self.set_slot(slot_id, Slot::EMPTY)?;
+        }
+
+        self.place(slot_id, record)?;
+        Ok(Some(slot_id))
+    }
+
+    /// Replaces the record of the slot. The record stays in place when it does
+    /// not grow and is moved inside the page otherwise. Returns `false`,
+    /// leaving the page unchanged, if the new record does not fit.
+    pub fn update(&mut self, slot_id: SlotId, record: &[u8]) -> io::Result<bool> {
+        let slot = self.live_slot(slot_id)?.ok_or_else(|| Self::not_found(slot_id))?;
+
+        if record.len() <= slot.record_len() {
+            self.0.write_bytes_at(slot.offset as usize, record)?;
+            self.set_slot(slot_id, Slot { offset: slot.offset, len: record.len() as u16 })?;
+            Ok(true)
+        } else {
+            // The slot is released first, so that compaction can reclaim the
+            // old copy of the record.
+            self.set_slot(slot_id, Slot::EMPTY)?;
+            if self.fits(record.len())? {
+                self.place(slot_id, record)?;
+                Ok(true)
+            } else {
+                self.set_slot(slot_id, slot)?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Deletes the record, leaving an empty slot behind. Records marked as
+    /// deleted can be deleted as well.
+    pub fn delete(&mut self, slot_id: SlotId) -> io::Result<()> {
+        self.stored_slot(slot_id)?.ok_or_else(|| Self::not_found(slot_id))?;
+        self.set_slot(slot_id, Slot::EMPTY)
+    }
+
+    /// Hides the record from readers without releasing its space, so that the
+    /// delete can still be rolled back.
+    pub fn mark_delete(&mut self, slot_id: SlotId) -> io::Result<()> {
+        let slot = self.live_slot(slot_id)?.ok_or_else(|| Self::not_found(slot_id))?;
+        self.set_slot(slot_id, Slot { offset: slot.offset, len: slot.len | Slot::DELETE_MASK })
+    }
+
+    pub fn rollback_delete(&mut self, slot_id: SlotId) -> io::Result<()> {
+        let slot = self.stored_slot(slot_id)?.ok_or_else(|| Self::not_found(slot_id))?;
+        self.set_slot(slot_id, Slot { offset: slot.offset, len: slot.len & !Slot::DELETE_MASK })
+    }
+
+    /// Moves all records to the back of the page, so that the free space is
+    /// contiguous, and drops the empty slots at the end of the directory.
+    pub fn compact(&mut self) -> io::Result<()> {
+        let mut slot_count = self.slot_count()?;
+        while slot_count > 0 && self.slot(slot_count - 1)?.is_empty() {
+            slot_count -= 1;
+        }
+        self.set_slot_count(slot_count)?;
+        self.compact_records()
+    }
+
+    fn compact_records(&mut self) -> io::Result<()> {
+        let slot_count = self.slot_count()?;
+        let mut slots = vec![];
+        for slot_id in 0..slot_count {
+            let slot = self.slot(slot_id)?;
+            if !slot.is_empty() {
+                slots.push((slot_id, slot));
+            }
+        }
+        slots.sort_by_key(|(_slot_id, slot)| std::cmp::Reverse(slot.offset));
+
+        let mut free_space_pointer = self.0.len();
+        for (slot_id, slot) in slots {
+            free_space_pointer -= slot.record_len();
+            self.0.copy_within(slot.offset as usize, free_space_pointer, slot.record_len())?;
+            self.set_slot(slot_id, Slot { offset: free_space_pointer as u16, len: slot.len })?;
+        }
+        self.set_free_space_pointer(free_space_pointer)
+    }
+
+    /// Writes the record to the free space, compacting the page if the free
+    /// space is fragmented. The caller has checked that the record fits.
+    fn place(&mut self, slot_id: SlotId, record: &[u8]) -> io::Result<()> {
+        let slots_end = Self::slots_end(self.slot_count()?);
+        if self.free_space_pointer()? < slots_end + record.len() {
+            self.compact_records()?;
+        }
+
+        let offset = self.free_space_pointer()? - record.len();
+        self.0.write_bytes_at(offset, record)?;
+        self.set_slot(slot_id, Slot { offset: offset as u16, len: record.len() as u16 })?;
+        self.set_free_space_pointer(offset)
+    }
+
+    fn set_slot_count(&mut self, slot_count: u16) -> io::Result<()> {
+        self.0.write_u16_at(Self::SLOT_COUNT_OFFSET, slot_count)
+    }
+
+    fn set_slot(&mut self, slot_id: SlotId, slot: Slot) -> io::Result<()> {
+        let offset = Self::slot_offset(slot_id);
+        self.0.write_u16_at(offset, slot.offset)?;
+        self.0.write_u16_at(offset + 2, slot.len)
+    }
+
+    fn set_free_space_pointer(&mut self, free_space_pointer: usize) -> io::Result<()> {
+        let mut header = self.0.read_header()?;
+        header.free_space_pointer = free_space_pointer as u16;
+        self.0.write_header(&header)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use crate::buffer::constants::PAGE_SIZE;
+    use crate::buffer::page::Page;
+    use crate::buffer::page_view::{PageType, PageView};
+    use crate::buffer::types::PageData;
+
+    use super::{Slot, SlottedPage, MAX_RECORD_SIZE};
+
+    fn empty_page(data: &mut PageData) -> io::Result<SlottedPage<&mut [u8]>> {
+        let mut page = SlottedPage::new(data.as_mut_slice());
+        page.init()?;
+        Ok(page)
+    }
+
+    #[test]
+    fn should_insert_get() -> io::Result<()> {
+        let mut data = [0; PAGE_SIZE];
+        let mut page = empty_page(&mut data)?;
+
+        assert_eq!(page.insert(b"hello")?, Some(0));
+        assert_eq!(page.insert(b"")?, Some(1));
+        assert_eq!(page.insert(b"world!")?, Some(2));
+
+        assert_eq!(page.get(0)?, Some(&b"hello"[..]));
+        assert_eq!(page.get(1)?, Some(&b""[..]));
+        assert_eq!(page.get(2)?, Some(&b"world!"[..]));
+        assert_eq!(page.get(3)?, None);
+        assert_eq!(page.slot_count()?, 3);
+        assert_eq!(PageView::new(data.as_slice()).read_header()?.page_type, PageType::Slotted);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_work_on_top_of_page() -> io::Result<()> {
+        let mut page = Page::new();
+
+        page.write_data(|data| {
+            let mut slotted_page = SlottedPage::new(data);
+            slotted_page.init()?;
+            slotted_page.insert(b"record")
+        })?;
+
+        assert!(page.is_dirty());
+        assert_eq!(
+            page.read_data(|data| SlottedPage::new(data).get(0).map(|r| r.map(<[u8]>::to_vec)))?,
+            Some(b"record".to_vec())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_account_free_space_exactly() -> io::Result<()> {
+        let mut data = [0; PAGE_SIZE];
+        let mut page = empty_page(&mut data)?;
+
+        assert_eq!(page.free_space()?, MAX_RECORD_SIZE);
+        assert_eq!(page.insert(&vec![1; MAX_RECORD_SIZE + 1])?, None);
+
+        assert_eq!(page.insert(&[1; 100])?, Some(0));
+        assert_eq!(page.free_space()?, MAX_RECORD_SIZE - 100 - Slot::SIZE);
+
+        let rest = page.free_space()?;
+        assert!(page.fits(rest)?);
+        assert!(!page.fits(rest + 1)?);
+        assert_eq!(page.insert(&vec![2; rest])?, Some(1));
+        assert_eq!(page.free_space()?, 0);
+        assert_eq!(page.insert(b"x")?, None);
+        assert_eq!(page.insert(b"")?, Some(2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_reuse_deleted_slot_and_space() -> io::Result<()> {
+        let mut data = [0; PAGE_SIZE];
+        let mut page = empty_page(&mut data)?;
+        let half = MAX_RECORD_SIZE / 2 - Slot::SIZE;
+
+        assert_eq!(page.insert(&vec![1; half])?, Some(0));
+        assert_eq!(page.insert(&vec![2; half])?, Some(1));
+        assert_eq!(page.insert(&vec![3; half])?, None);
+
+        page.delete(0)?;
+        assert_eq!(page.get(0)?, None);
+        assert_eq!(page.delete(0).unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(page.free_space()?, half + Slot::SIZE);
+
+        assert_eq!(page.insert(&vec![3; half])?, Some(0));
+        assert_eq!(page.get(0)?, Some(&vec![3; half][..]));
+        assert_eq!(page.get(1)?, Some(&vec![2; half][..]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_update_in_place() -> io::Result<()> {
+        let mut data = [0; PAGE_SIZE];
+        let mut page = empty_page(&mut data)?;
+
+        page.insert(b"first")?;
+        page.insert(b"second")?;
+        let free_space = page.free_space()?;
+
+        assert!(page.update(0, b"1st")?);
+        assert_eq!(page.get(0)?, Some(&b"1st"[..]));
+        assert_eq!(page.get(1)?, Some(&b"second"[..]));
+        assert_eq!(page.free_space()?, free_space + 2);
+        assert_eq!(page.update(2, b"").unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_update_with_relocation() -> io::Result<()> {
+        let mut data = [0; PAGE_SIZE];
+        let mut page = empty_page(&mut data)?;
+        let third = MAX_RECORD_SIZE / 3 - Slot::SIZE;
+
+        page.insert(&vec![1; third])?;
+        page.insert(&vec![2; third])?;
+        page.insert(&vec![3; third])?;
+        let free_space = page.free_space()?;
+
+        assert!(page.update(1, &vec![4; third + free_space])?);
+        assert_eq!(page.get(0)?, Some(&vec![1; third][..]));
+        assert_eq!(page.get(1)?, Some(&vec![4; third + free_space][..]));
+        assert_eq!(page.get(2)?, Some(&vec![3; third][..]));
+        assert_eq!(page.free_space()?, 0);
+
+        // The slot of the updated record is reused, so its size is available
+        // too.
+        assert!(!page.update(0, &vec![5; third + Slot::SIZE + 1])?);
+        assert_eq!(page.get(0)?, Some(&vec![1; third][..]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_mark_delete_and_apply() -> io::Result<()> {
+        let mut data = [0; PAGE_SIZE];
+        let mut page = empty_page(&mut data)?;
+
+        page.insert(b"first")?;
+        page.insert(b"second")?;
+        let free_space = page.free_space()?;
+
+        page.mark_delete(0)?;
+        assert_eq!(page.get(0)?, None);
+        assert_eq!(page.free_space()?, free_space);
+        assert_eq!(page.update(0, b"").unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(page.mark_delete(0).unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        page.rollback_delete(0)?;
+        assert_eq!(page.get(0)?, Some(&b"first"[..]));
+
+        page.mark_delete(1)?;
+        page.compact()?;
+        page.delete(1)?;
+        assert_eq!(page.get(0)?, Some(&b"first"[..]));
+        assert_eq!(page.free_space()?, free_space + 6 + Slot::SIZE);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_link_pages() -> io::Result<()> {
+        let mut data = [0; PAGE_SIZE];
+        let mut page = empty_page(&mut data)?;
+
+        assert_eq!(page.next_page_id()?, None);
+        page.set_next_page_id(Some(12))?;
+        assert_eq!(page.next_page_id()?, Some(12));
+        page.set_next_page_id(None)?;
+        assert_eq!(page.next_page_id()?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_compact() -> io::Result<()> {
+        let mut data = [0; PAGE_SIZE];
+        let mut page = empty_page(&mut data)?;
+
+        for i in 0..10u8 {
+            page.insert(&[i; 10])?;
+        }
+        for slot_id in [1, 4, 8, 9] {
+            page.delete(slot_id)?;
+        }
+        let free_space = page.free_space()?;
+
+        page.compact()?;
+        assert_eq!(page.slot_count()?, 8);
+        assert_eq!(page.free_space()?, free_space + 2 * Slot::SIZE);
+        for i in [0, 2, 3, 5, 6, 7] {
+            assert_eq!(page.get(i)?, Some(&[i as u8; 10][..]));
+        }
+        assert_eq!(PageView::new(data.as_slice()).read_header()?.free_space_pointer as usize, PAGE_SIZE - 60);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,637 @@
+use std::io::{self, Read};
+
+use crate::buffer::buffer_pool::{no_free_frame, page_not_deallocated};
+use crate::buffer::buffer_pool_instance::BufferPoolInstance;
+use crate::buffer::page_view::PageView;
+use crate::buffer::types::PageId;
+use crate::storage::overflow::{self, OverflowReader};
+use crate::storage::slotted_page::{SlotId, SlottedPage, MAX_RECORD_SIZE};
+
+/// The address of a tuple in a table heap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RecordId {
+    pub page_id: PageId,
+    pub slot_id: SlotId,
+}
+
+/// A table stored as a linked list of slotted pages. New tuples are appended to
+/// the last page of the list, and a new page is linked when it is full.
+///
+/// Every record starts with a tag byte. Tuples that do not fit into an empty
+/// page are spilled to a chain of overflow pages, and the record only keeps the
+/// first page of the chain and the length of the tuple.
+///
+/// Clones share the pages. Each one remembers the last page it has seen, and
+/// follows the pages other clones linked after it before appending one.
+#[derive(Clone)]
+pub struct TableHeap {
+    pool: BufferPoolInstance,
+    first_page_id: PageId,
+    last_page_id: PageId,
+}
+
+const INLINE_TAG: u8 = 0;
+const OVERFLOW_TAG: u8 = 1;
+
+enum StoredTuple {
+    Inline(Vec<u8>),
+    Overflow { first_page_id: PageId, len: usize },
+}
+
+impl StoredTuple {
+    fn decode(record: &[u8]) -> io::Result<StoredTuple> {
+        let view = PageView::new(record);
+        match view.read_u8_at(0)? {
+            INLINE_TAG => Ok(StoredTuple::Inline(record[1..].to_vec())),
+            OVERFLOW_TAG => Ok(StoredTuple::Overflow {
+                first_page_id: view.read_u64_at(1)? as PageId,
+                len: view.read_u64_at(9)? as usize,
+            }),
+            tag => Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown tuple tag {}", tag))),
+        }
+    }
+
+    fn encode_overflow(first_page_id: PageId, len: usize) -> Vec<u8> {
+        let mut record = vec![OVERFLOW_TAG];
+        record.extend_from_slice(&(first_page_id as u64).to_le_bytes());
+        record.extend_from_slice(&(len as u64).to_le_bytes());
+        record
+    }
+
+    fn encode_inline(tuple: &[u8]) -> Vec<u8> {
+        let mut record = Vec::with_capacity(tuple.len() + 1);
+        record.push(INLINE_TAG);
+        record.extend_from_slice(tuple);
+        record
+    }
+
+    fn read(self, pool: &mut BufferPoolInstance) -> io::Result<Vec<u8>> {
+        match self {
+            StoredTuple::Inline(tuple) => Ok(tuple),
+            StoredTuple::Overflow { first_page_id, .. } => overflow::read_chain(pool, first_page_id),
+        }
+    }
+}
+
+/// Streams a tuple, see `TableHeap::tuple_reader`.
+pub enum TupleReader {
+    Inline(io::Cursor<Vec<u8>>),
+    Overflow(OverflowReader),
+}
+
+impl Read for TupleReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            TupleReader::Inline(cursor) => cursor.read(buf),
+            TupleReader::Overflow(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl TableHeap {
+    pub fn create(mut pool: BufferPoolInstance) -> io::Result<Self> {
+        let first_page_id = new_slotted_page(&mut pool)?;
+        pool.unpin_page(first_page_id, true)?;
+        Ok(TableHeap { pool, first_page_id, last_page_id: first_page_id })
+    }
+
+    pub fn open(mut pool: BufferPoolInstance, first_page_id: PageId) -> io::Result<Self> {
+        let mut last_page_id = first_page_id;
+        while let Some(next_page_id) = read_page(&mut pool, last_page_id, |page| page.next_page_id())? {
+            last_page_id = next_page_id;
+        }
+        Ok(TableHeap { pool, first_page_id, last_page_id })
+    }
+
+    pub fn first_page_id(&self) -> PageId {
+        self.first_page_id
+    }
+
+    pub fn insert_tuple(&mut self, tuple: &[u8]) -> io::Result<RecordId> {
+        let record = self.encode(tuple)?;
+        let inserted = self.insert_record(&record);
+        if inserted.is_err() {
+            self.free_overflow(&record)?;
+        }
+        inserted
+    }
+
+    /// Returns the tuple, or `None` if it was deleted.
+    pub fn get_tuple(&mut self, rid: RecordId) -> io::Result<Option<Vec<u8>>> {
+        match self.get_stored(rid)? {
+            Some(stored) => stored.read(&mut self.pool).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns a reader over the tuple that loads a spilled tuple one page at a
+    /// time, or `None` if the tuple was deleted.
+    pub fn tuple_reader(&mut self, rid: RecordId) -> io::Result<Option<TupleReader>> {
+        Ok(self.get_stored(rid)?.map(|stored| match stored {
+            StoredTuple::Inline(tuple) => TupleReader::Inline(io::Cursor::new(tuple)),
+            StoredTuple::Overflow { first_page_id, .. } => {
+                TupleReader::Overflow(OverflowReader::new(self.pool.clone(), first_page_id))
+            }
+        }))
+    }
+
+    /// Replaces the tuple in its page. Returns `false` if the new tuple does
+    /// not fit into the page, in which case the caller has to delete the
+    /// tuple and insert it again.
+    pub fn update_tuple(&mut self, rid: RecordId, tuple: &[u8]) -> io::Result<bool> {
+        let record = self.encode(tuple)?;
+        // The old record is taken under the same latch that replaces it, so
+        // that only one update or delete frees its overflow pages.
+        let replaced = write_page(&mut self.pool, rid.page_id, |page| {
+            let old_record = page.get(rid.slot_id)?.map(<[u8]>::to_vec);
+            let updated = page.update(rid.slot_id, &record)?;
+            Ok(old_record.filter(|_| updated))
+        });
+
+        match replaced {
+            Ok(Some(old_record)) => {
+                self.free_overflow(&old_record)?;
+                Ok(true)
+            }
+            Ok(None) => {
+                self.free_overflow(&record)?;
+                Ok(false)
+            }
+            Err(e) => {
+                self.free_overflow(&record)?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Hides the tuple from readers. Its space is reclaimed by `apply_delete`.
+    pub fn mark_delete(&mut self, rid: RecordId) -> io::Result<()> {
+        write_page(&mut self.pool, rid.page_id, |page| page.mark_delete(rid.slot_id))
+    }
+
+    pub fn rollback_delete(&mut self, rid: RecordId) -> io::Result<()> {
+        write_page(&mut self.pool, rid.page_id, |page| page.rollback_delete(rid.slot_id))
+    }
+
+    /// Deletes the tuple together with its overflow pages.
+    pub fn apply_delete(&mut self, rid: RecordId) -> io::Result<()> {
+        let record = write_page(&mut self.pool, rid.page_id, |page| {
+            let record = page.get_stored(rid.slot_id)?.map(<[u8]>::to_vec);
+            page.delete(rid.slot_id)?;
+            Ok(record)
+        })?;
+        record.map_or(Ok(()), |record| self.free_overflow(&record))
+    }
+
+    /// Iterates over the live tuples in page order.
+    pub fn iter(&self) -> TableIterator {
+        TableIterator { pool: self.pool.clone(), page_id: Some(self.first_page_id), slot_id: 0 }
+    }
+
+    /// Zeroes every page of the heap, overflow pages included, and drops them
+    /// from the buffer pool. The heap pages are deallocated, so new pages
+    /// reuse them. Meant for temporary heaps, e.g. spilled rows.
+    pub fn free(mut self) -> io::Result<()> {
+        let mut page_id = Some(self.first_page_id);
+        while let Some(current) = page_id {
+            let mut page = self.pool.fetch_page(current)?.ok_or_else(no_free_frame)?;
+            let read: io::Result<(Vec<Vec<u8>>, _)> = page.read_data(|data| {
+                let page = SlottedPage::new(data);
+                let mut records = vec![];
+                for slot_id in 0..page.slot_count()? {
+                    records.extend(page.get_stored(slot_id)?.map(<[u8]>::to_vec));
+                }
+                Ok((records, page.next_page_id()?))
+            });
+            if read.is_ok() {
+                page.write_data(|data| data.fill(0));
+            }
+            self.pool.unpin_page(current, true)?;
+            if !self.pool.deallocate_page(current) {
+                return Err(page_not_deallocated(current));
+            }
+
+            let (records, next_page_id) = read?;
+            for record in records {
+                self.free_overflow(&record)?;
+            }
+            page_id = next_page_id;
+        }
+        Ok(())
+    }
+
+    fn encode(&mut self, tuple: &[u8]) -> io::Result<Vec<u8>> {
+        if tuple.len() < MAX_RECORD_SIZE {
+            Ok(StoredTuple::encode_inline(tuple))
+        } else {
+            let first_page_id = overflow::write_chain(&mut self.pool, tuple)?;
+            Ok(StoredTuple::encode_overflow(first_page_id, tuple.len()))
+        }
+    }
+
+    fn free_overflow(&mut self, record: &[u8]) -> io::Result<()> {
+        match StoredTuple::decode(record)? {
+            StoredTuple::Overflow { first_page_id, .. } => overflow::free_chain(&mut self.pool, first_page_id),
+            StoredTuple::Inline(_) => Ok(()),
+        }
+    }
+
+    fn get_stored(&mut self, rid: RecordId) -> io::Result<Option<StoredTuple>> {
+        read_page(&mut self.pool, rid.page_id, |page| page.get(rid.slot_id)?.map(StoredTuple::decode).transpose())
+    }
+
+    fn insert_record(&mut self, record: &[u8]) -> io::Result<RecordId> {
+        loop {
+            let page_id = self.last_page_id;
+            let (slot_id, next_page_id) = write_page(&mut self.pool, page_id, |page| {
+                let slot_id = page.insert(record)?;
+                Ok((slot_id, page.next_page_id()?))
+            })?;
+
+            match (slot_id, next_page_id) {
+                (Some(slot_id), _) => return Ok(RecordId { page_id, slot_id }),
+                (None, Some(next_page_id)) => self.last_page_id = next_page_id,
+                (None, None) => self.append_page()?,
+            }
+        }
+    }
+
+    /// Links a new page after the last one, unless another clone of the heap
+    /// has linked one since, and moves on to the page linked.
+    fn append_page(&mut self) -> io::Result<()> {
+        let new_page_id = new_slotted_page(&mut self.pool)?;
+        let linked = write_page(&mut self.pool, self.last_page_id, |page| match page.next_page_id()? {
+            Some(next_page_id) => Ok(next_page_id),
+            None => page.set_next_page_id(Some(new_page_id)).map(|_| new_page_id),
+        });
+        self.pool.unpin_page(new_page_id, true)?;
+        let is_linked = matches!(linked, Ok(next_page_id) if next_page_id == new_page_id);
+        if !is_linked && !self.pool.deallocate_page(new_page_id) {
+            return Err(page_not_deallocated(new_page_id));
+        }
+
+        self.last_page_id = linked?;
+        Ok(())
+    }
+}
+
+pub struct TableIterator {
+    pool: BufferPoolInstance,
+    page_id: Option<PageId>,
+    slot_id: SlotId,
+}
+
+impl TableIterator {
+    fn next_stored(&mut self) -> io::Result<Option<(RecordId, StoredTuple)>> {
+        while let Some(page_id) = self.page_id {
+            let start = self.slot_id;
+            let found = read_page(&mut self.pool, page_id, |page| {
+                for slot_id in start..page.slot_count()? {
+                    if let Some(record) = page.get(slot_id)? {
+                        return Ok(Ok((slot_id, StoredTuple::decode(record)?)));
+                    }
+                }
+                Ok(Err(page.next_page_id()?))
+            })?;
+
+            match found {
+                Ok((slot_id, stored)) => {
+                    self.slot_id = slot_id + 1;
+                    return Ok(Some((RecordId { page_id, slot_id }, stored)));
+                }
+                Err(next_page_id) => {
+                    self.page_id = next_page_id;
+                    self.slot_id = 0;
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Iterator for TableIterator {
+    type Item = io::Result<(RecordId, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.next_stored().and_then(|next| {
+            next.map(|(rid, stored)| stored.read(&mut self.pool).map(|tuple| (rid, tuple))).transpose()
+        });
+        if next.is_err() {
+            self.page_id = None;
+        }
+        next.transpose()
+    }
+}
+
+/// Allocates and formats a new slotted page. The page is returned pinned.
+fn new_slotted_page(pool: &mut BufferPoolInstance) -> io::Result<PageId> {
+    let mut page = pool.new_page()?.ok_or_else(no_free_frame)?;
+    let page_id = page.get_page_id().unwrap();
+    if let Err(e) = page.write_data(|data| SlottedPage::new(data).init()) {
+        pool.unpin_page(page_id, false)?;
+        if !pool.deallocate_page(page_id) {
+            return Err(page_not_deallocated(page_id));
+        }
+        return Err(e);
+    }
+    Ok(page_id)
+}
+
+fn read_page<F, R>(pool: &mut BufferPoolInstance, page_id: PageId, f: F) -> io::Result<R>
+where
+    F: FnOnce(&SlottedPage<&[u8]>) -> io::Result<R>,
+{
+    let page = pool.fetch_page(page_id)?.ok_or_else(no_free_frame)?;
+    let result = page.read_data(|data| f(&SlottedPage::new(data)));
+    pool.unpin_page(page_id, false)?;
+    result
+}
+
+fn write_page<F, R>(pool: &mut BufferPoolInstance, page_id: PageId, f: F) -> io::Result<R>
+where
+    F: FnOnce(&mut SlottedPage<&mut [u8]>) -> io::Result<R>,
+{
+    let mut page = pool.fetch_page(page_id)?.ok_or_else(no_free_frame)?;
+    let result = page.write_data(|data| f(&mut SlottedPage::new(data)));
+    pool.unpin_page(page_id, true)?;
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::{self, Read};
+
+    use crate::buffer::buffer_pool_instance::BufferPoolInstance;
+    use crate::buffer::constants::PAGE_SIZE;
+    use crate::buffer::disk_manager::DiskManager;
+    use crate::buffer::page_view::{PageType, PageView};
+    use crate::buffer::test_utils::TempFile;
+    use crate::buffer::types::PageId;
+    use crate::storage::overflow;
+    use crate::storage::slotted_page::MAX_RECORD_SIZE;
+    use crate::types::{check_random, thread, thread_rng, RngCore};
+
+    use super::{RecordId, StoredTuple, TableHeap};
+
+    fn tuple(i: usize) -> Vec<u8> {
+        format!("tuple-{}", i).repeat(i % 7 + 1).into_bytes()
+    }
+
+    fn random_tuple(len: usize) -> Vec<u8> {
+        let mut tuple = vec![0; len];
+        thread_rng().fill_bytes(&mut tuple);
+        tuple
+    }
+
+    /// The pages of the overflow chain of a tuple, following the chain from the
+    /// stored record.
+    fn overflow_page_ids(heap: &mut TableHeap, rid: RecordId) -> io::Result<Vec<PageId>> {
+        let mut page_ids = vec![];
+        let mut page_id = match heap.get_stored(rid)? {
+            Some(StoredTuple::Overflow { first_page_id, .. }) => Some(first_page_id),
+            _ => None,
+        };
+        while let Some(current) = page_id {
+            page_ids.push(current);
+            let page = heap.pool.fetch_page(current)?.unwrap();
+            page_id = page.read_data(|data| overflow::read_page(data, &mut vec![]))?;
+            heap.pool.unpin_page(current, false)?;
+        }
+        Ok(page_ids)
+    }
+
+    fn overflow_page_type(pool: &BufferPoolInstance, page_id: PageId) -> io::Result<PageType> {
+        let mut pool = pool.clone();
+        let page = pool.fetch_page(page_id)?.unwrap();
+        let page_type = page.read_data(|data| PageView::new(data).read_header().map(|header| header.page_type));
+        pool.unpin_page(page_id, false)?;
+        page_type
+    }
+
+    fn is_deallocated(pool: &BufferPoolInstance, page_id: PageId) -> bool {
+        let fetched = pool.clone().fetch_page(page_id);
+        fetched.err().map(|err| err.kind()) == Some(io::ErrorKind::InvalidInput)
+    }
+
+    #[test]
+    fn should_insert_get_across_pages() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 3);
+        let mut heap = TableHeap::create(pool)?;
+
+        let rids: Vec<RecordId> = (0..500).map(|i| heap.insert_tuple(&tuple(i))).collect::<io::Result<_>>()?;
+        assert!(rids.last().unwrap().page_id > heap.first_page_id());
+
+        for (i, rid) in rids.iter().enumerate() {
+            assert_eq!(heap.get_tuple(*rid)?, Some(tuple(i)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn should_iterate_live_tuples() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 3);
+        let mut heap = TableHeap::create(pool)?;
+
+        let rids: Vec<RecordId> = (0..300).map(|i| heap.insert_tuple(&tuple(i))).collect::<io::Result<_>>()?;
+        for rid in rids.iter().step_by(3) {
+            heap.mark_delete(*rid)?;
+        }
+        heap.rollback_delete(rids[0])?;
+
+        let tuples: Vec<(RecordId, Vec<u8>)> = heap.iter().collect::<io::Result<_>>()?;
+        let expected: Vec<(RecordId, Vec<u8>)> =
+            rids.iter().enumerate().filter(|(i, _)| i % 3 != 0 || *i == 0).map(|(i, rid)| (*rid, tuple(i))).collect();
+        assert_eq!(tuples, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_update_and_delete() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 2);
+        let mut heap = TableHeap::create(pool)?;
+
+        let rid = heap.insert_tuple(b"short")?;
+        assert!(heap.update_tuple(rid, b"a longer tuple")?);
+        assert_eq!(heap.get_tuple(rid)?, Some(b"a longer tuple".to_vec()));
+        let filler = heap.insert_tuple(&[1; MAX_RECORD_SIZE - 100])?;
+        assert_eq!(filler.page_id, rid.page_id);
+        assert!(!heap.update_tuple(rid, &[0; 200])?);
+        assert_eq!(heap.get_tuple(rid)?, Some(b"a longer tuple".to_vec()));
+        heap.apply_delete(filler)?;
+
+        heap.mark_delete(rid)?;
+        assert_eq!(heap.get_tuple(rid)?, None);
+        heap.apply_delete(rid)?;
+        assert_eq!(heap.apply_delete(rid).unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        assert_eq!(heap.insert_tuple(b"reused")?, rid);
+        Ok(())
+    }
+
+    #[test]
+    fn should_spill_tuples_larger_than_page() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 2);
+        let mut heap = TableHeap::create(pool)?;
+
+        let large = random_tuple(3 * PAGE_SIZE + 5);
+        let small = heap.insert_tuple(b"first")?;
+        let rid = heap.insert_tuple(&large)?;
+        assert_eq!(rid.page_id, heap.first_page_id());
+        let overflow_page_ids = overflow_page_ids(&mut heap, rid)?;
+        assert_eq!(overflow_page_ids.len(), 4);
+        let next = heap.insert_tuple(&random_tuple(MAX_RECORD_SIZE - 1))?;
+        assert_ne!(next.page_id, heap.first_page_id());
+        assert!(!overflow_page_ids.contains(&next.page_id));
+
+        assert_eq!(heap.get_tuple(rid)?, Some(large.clone()));
+        assert_eq!(heap.get_tuple(small)?, Some(b"first".to_vec()));
+        let tuples: Vec<Vec<u8>> = heap.iter().map(|tuple| tuple.map(|(_, tuple)| tuple)).collect::<io::Result<_>>()?;
+        assert_eq!(tuples[1], large);
+
+        let mut streamed = vec![];
+        heap.tuple_reader(rid)?.unwrap().read_to_end(&mut streamed)?;
+        assert_eq!(streamed, large);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_free_overflow_pages() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 2);
+        let mut heap = TableHeap::create(pool.clone())?;
+
+        let rid = heap.insert_tuple(&random_tuple(2 * PAGE_SIZE))?;
+        let first_chain = overflow_page_ids(&mut heap, rid)?;
+        assert_eq!(first_chain.len(), 3);
+
+        let replacement = random_tuple(PAGE_SIZE);
+        assert!(heap.update_tuple(rid, &replacement)?);
+        assert_eq!(heap.get_tuple(rid)?, Some(replacement));
+        for page_id in &first_chain {
+            assert!(is_deallocated(&pool, *page_id));
+        }
+
+        let second_chain = overflow_page_ids(&mut heap, rid)?;
+        assert_eq!(second_chain.len(), 2);
+        assert!(heap.update_tuple(rid, b"inline")?);
+        assert!(overflow_page_ids(&mut heap, rid)?.is_empty());
+        for page_id in &second_chain {
+            assert!(is_deallocated(&pool, *page_id));
+        }
+
+        let rid = heap.insert_tuple(&random_tuple(PAGE_SIZE))?;
+        let chain = overflow_page_ids(&mut heap, rid)?;
+        assert!(chain.iter().all(|page_id| first_chain.contains(page_id) || second_chain.contains(page_id)));
+        for page_id in &chain {
+            assert_eq!(overflow_page_type(&pool, *page_id)?, PageType::Overflow);
+        }
+        heap.mark_delete(rid)?;
+        heap.apply_delete(rid)?;
+        for page_id in &chain {
+            assert!(is_deallocated(&pool, *page_id));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_reopen_heap() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 2);
+        let mut heap = TableHeap::create(pool.clone())?;
+        let rids: Vec<RecordId> = (0..200).map(|i| heap.insert_tuple(&tuple(i))).collect::<io::Result<_>>()?;
+
+        let mut reopened = TableHeap::open(pool, heap.first_page_id())?;
+        let rid = reopened.insert_tuple(b"appended")?;
+        assert_eq!(rid.page_id, rids.last().unwrap().page_id);
+        assert_eq!(reopened.iter().count(), 201);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_free_heap_pages() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 2);
+        let mut heap = TableHeap::create(pool.clone())?;
+
+        let rids: Vec<RecordId> = (0..300).map(|i| heap.insert_tuple(&tuple(i))).collect::<io::Result<_>>()?;
+        let large = heap.insert_tuple(&random_tuple(2 * PAGE_SIZE))?;
+        let chain = overflow_page_ids(&mut heap, large)?;
+        assert_eq!(chain.len(), 3);
+        let last_page_id = rids.last().unwrap().page_id;
+        let first_page_id = heap.first_page_id();
+        heap.free()?;
+
+        for page_id in [first_page_id, last_page_id].into_iter().chain(chain) {
+            assert!(is_deallocated(&pool, page_id));
+        }
+        assert!(pool.dump_pinned().is_empty());
+
+        // A new heap takes the freed pages first, so the file does not grow.
+        let len = fs::metadata(file.path())?.len();
+        let mut heap = TableHeap::create(pool)?;
+        assert_eq!(heap.first_page_id(), first_page_id);
+        for i in 0..300 {
+            heap.insert_tuple(&tuple(i))?;
+        }
+        heap.insert_tuple(&random_tuple(2 * PAGE_SIZE))?;
+        heap.free()?;
+        assert_eq!(fs::metadata(file.path())?.len(), len);
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_report_pinned_heap_page_on_free() -> io::Result<()> {
+        let file = TempFile::new()?;
+        let mut pool = BufferPoolInstance::new_simple(DiskManager::new(file.path())?, 2);
+        let heap = TableHeap::create(pool.clone())?;
+        let first_page_id = heap.first_page_id();
+
+        assert!(pool.fetch_page(first_page_id)?.is_some());
+        let error = heap.free().unwrap_err();
+        assert!(error.to_string().contains(&format!("page {} is pinned", first_page_id)));
+        assert!(!is_deallocated(&pool, first_page_id));
+        pool.unpin_page(first_page_id, false)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn should_append_pages_from_clones_concurrently() {
+        check_random(
+            || {
+                let file = TempFile::new().unwrap();
+                let pool = BufferPoolInstance::new_simple(DiskManager::new(file.path()).unwrap(), 4);
+                let heap = TableHeap::create(pool).unwrap();
+
+                let handles: Vec<_> = (0..2)
+                    .map(|n| {
+                        let mut heap = heap.clone();
+                        thread::spawn(move || {
+                            for i in 0..20 {
+                                heap.insert_tuple(&random_tuple(PAGE_SIZE / 8 + n * 20 + i)).unwrap();
+                            }
+                        })
+                    })
+                    .collect();
+                for handle in handles {
+                    handle.join().unwrap();
+                }
+
+                let mut lens: Vec<usize> = heap.iter().map(|tuple| tuple.unwrap().1.len()).collect();
+                lens.sort();
+                assert_eq!(lens, (0..40).map(|i| PAGE_SIZE / 8 + i).collect::<Vec<_>>());
+            },
+            100,
+        )
+    }
+}
@@ -8,7 +8,7 @@ pub(crate) use shuttle::rand::Rng;
 #[cfg(shuttle)]
 pub(crate) use shuttle::rand::RngCore;
 #[cfg(shuttle)]
-pub(crate) use shuttle::sync::{Arc, Mutex, RwLock};
+pub(crate) use shuttle::sync::{Arc, Condvar, Mutex, RwLock};
 #[cfg(shuttle)]
 pub(crate) use shuttle::thread;
 
@@ -20,7 +20,7 @@ pub(crate) use rand::Rng;
 #[cfg(not(shuttle))]
 pub(crate) use rand::RngCore;
 #[cfg(not(shuttle))]
-pub(crate) use std::sync::{Arc, Mutex, RwLock};
+pub(crate) use std::sync::{Arc, Condvar, Mutex, RwLock};
 #[cfg(not(shuttle))]
 pub(crate) use std::thread;
 